@@ -10,7 +10,7 @@ use std::{
 use futures_io::{AsyncRead, AsyncWrite};
 use pin_project_lite::pin_project;
 
-use crate::{CreateRange, ImageDimension, NonZeroRange};
+use crate::{BuildError, CreateRange, ImageDimension, NonZeroRange, Span, span::cut_span};
 
 const U32_SIZE: usize = std::mem::size_of::<u32>();
 const U64_SIZE: usize = std::mem::size_of::<u64>();
@@ -272,12 +272,11 @@ where
                             *this.pending_range
                         {
                             if start < pending_actual_end {
-                                return Poll::Ready(Err(io::Error::new(
-                                    io::ErrorKind::InvalidData,
-                                    format!(
-                                        "Range start {start} is before previous end {pending_actual_end}"
-                                    ),
-                                )));
+                                return Poll::Ready(Err(BuildError::Overlap {
+                                    start,
+                                    previous_end: pending_actual_end,
+                                }
+                                .into()));
                             }
                             let pending_ends_at_line_end =
                                 ox > 0 && (pending_actual_end + ox) % width == 0;
@@ -301,13 +300,11 @@ where
                             }
                         } else {
                             if start < *this.last_end {
-                                return Poll::Ready(Err(io::Error::new(
-                                    io::ErrorKind::InvalidData,
-                                    format!(
-                                        "Range start {start} is before previous end {}",
-                                        *this.last_end
-                                    ),
-                                )));
+                                return Poll::Ready(Err(BuildError::Overlap {
+                                    start,
+                                    previous_end: *this.last_end,
+                                }
+                                .into()));
                             }
                             *this.pending_range = Some((start, len, end, *this.last_end));
                             *this.last_end = end;
@@ -342,12 +339,6 @@ where
                 }
                 WriterState::Closing => {
                     ready!(this.writer.as_mut().poll_close(cx))?;
-                    if *this.last_end == 0 {
-                        return Poll::Ready(Err(io::Error::new(
-                            io::ErrorKind::InvalidInput,
-                            "Expected at least 1 range",
-                        )));
-                    }
                     *this.state = WriterState::Done;
                 }
                 WriterState::Done => {
@@ -454,6 +445,14 @@ impl<R: AsyncRead + Unpin> AsyncRangeStream<R> {
             ..self
         }
     }
+
+    /// Streams spans in the coordinates of the parent image. Ranges are cut at the end of each row
+    pub fn into_span_stream(self) -> AsyncSpanStream<R> {
+        AsyncSpanStream {
+            inner: self.into_roi_stream(),
+            pending: None,
+        }
+    }
 }
 
 impl<R: AsyncRead> futures_core::Stream for AsyncRangeStream<R> {
@@ -539,6 +538,43 @@ impl<R: AsyncRead> futures_core::Stream for AsyncRangeStream<R> {
     }
 }
 
+pin_project! {
+    /// See `AsyncRangeStream::into_span_stream`
+    pub struct AsyncSpanStream<R> {
+        #[pin] inner: AsyncRangeStream<R>,
+        pending: Option<NonZeroRange<u64>>,
+    }
+}
+
+impl<R> ImageDimension for AsyncSpanStream<R> {
+    fn bounds(&self) -> crate::Rect<u32> {
+        self.inner.bounds()
+    }
+
+    fn width(&self) -> std::num::NonZero<u32> {
+        self.inner.width()
+    }
+}
+
+impl<R: AsyncRead> futures_core::Stream for AsyncSpanStream<R> {
+    type Item = io::Result<Span<u64>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        let range = match this.pending.take() {
+            Some(range) => range,
+            None => match ready!(this.inner.as_mut().poll_next(cx)) {
+                Some(Ok(range)) => range,
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            },
+        };
+        let (span, rest) = cut_span(range, this.inner.bounds());
+        *this.pending = rest;
+        Poll::Ready(Some(Ok(span)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -618,16 +654,18 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn write_empty_error() {
+    async fn write_empty_roundtrip() {
         let input: [RangeInclusive<u64>; 0] = [];
-        let writer = Vec::new();
-        let _err = AsyncRangeWriter::new(
-            writer,
-            with_1000_roi(input),
-            // Roi::new(0, 0, NonZeroU32::MIN, NonZeroU32::MIN),
-        )
-        .await
-        .unwrap_err();
+        let mut buf = Vec::new();
+        AsyncRangeWriter::new(&mut buf, with_1000_roi(input))
+            .await
+            .unwrap();
+        assert_eq!(HEADER_SIZE, buf.len());
+
+        let reader = AsyncRangeStream::new(&buf[..]).await.unwrap();
+        assert_eq!(ROI, reader.bounds());
+        let result: Vec<_> = reader.try_collect().await.unwrap();
+        assert!(result.is_empty());
     }
 
     #[tokio::test]
@@ -647,8 +685,15 @@ mod tests {
             with_1000_roi(ranges),
             // Roi::new(0, 0, NonZeroU32::MIN, NonZeroU32::MIN),
         );
-        let result = writer.await;
-        assert!(matches!(result, Err(e) if e.kind() == ErrorKind::InvalidData));
+        let e = writer.await.unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+        assert_eq!(
+            Some(&BuildError::Overlap {
+                start: 15,
+                previous_end: 21
+            }),
+            e.get_ref().and_then(|e| e.downcast_ref::<BuildError>())
+        );
     }
 
     #[tokio::test]
@@ -911,6 +956,20 @@ mod tests {
         assert_eq!(global_result, global_ranges);
     }
 
+    #[tokio::test]
+    async fn span_stream_uses_parent_coordinates() -> TestResult {
+        let height = NonZeroU32::new(2).unwrap();
+        let mut buf = make_header_bytes(2, 1, NonZeroU32::new(4).unwrap(), height);
+        buf.extend_from_slice(&make_range_bytes(1, 5));
+        let spans: Vec<_> = AsyncRangeStream::new(&buf[..])
+            .await?
+            .into_span_stream()
+            .try_collect()
+            .await?;
+        assert_eq!(vec![Span::new(3..6, 1), Span::new(2..4, 2)], spans);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn reader_roi_forwarded_to_writer() -> TestResult {
         use crate::{Rect, SortedRanges};
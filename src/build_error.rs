@@ -0,0 +1,119 @@
+use std::{io, num::NonZeroU32};
+
+use crate::Rect;
+
+/// Reasons, why ranges cannot be collected into `SortedRanges` or `SortedRangesMap`
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum BuildError {
+    #[error("Range {start}..{end} is empty")]
+    EmptyRange { start: u64, end: u64 },
+    /// Ranges must be ordered and are not allowed to touch
+    #[error(
+        "Range starting at {start} overlaps or touches the previous range ending at {previous_end}"
+    )]
+    Overlap { start: u64, previous_end: u64 },
+    #[error("Gap of {gap} doesn't fit into the excluded type")]
+    GapOverflow { gap: u64 },
+    #[error("Length of {len} doesn't fit into the included type")]
+    LengthOverflow { len: u64 },
+    #[error("Height of {height} doesn't fit into u32")]
+    HeightOverflow { height: u64 },
+    /// Scaled ROI offsets must fit into u32
+    #[error("Offset of {offset} doesn't fit into u32")]
+    OffsetOverflow { offset: u64 },
+    /// Borrowed slices must contain one excluded value per included value
+    #[error("Got {included} included but {excluded} excluded values")]
+    LengthMismatch { included: usize, excluded: usize },
+    #[error("Ranges end at {end}, but the ROI only contains {size} pixels")]
+    OutOfBounds { end: u64, size: u64 },
+    /// Edits can't re-encode the continuation entries of
+    /// `SortedRanges::try_from_ordered_iter_roi_split`
+    #[error("Masks with split encoding can't be edited")]
+    SplitEncoding,
+    /// Flips and rotations need the ROI to lie within the parent image
+    #[error("ROI {roi:?} exceeds the image width of {image_width}")]
+    RoiExceedsImageWidth {
+        roi: Rect<u32>,
+        image_width: NonZeroU32,
+    },
+    #[error("ROI {roi:?} exceeds the image height of {image_height}")]
+    RoiExceedsImageHeight {
+        roi: Rect<u32>,
+        image_height: NonZeroU32,
+    },
+    /// Interned meta contains more distinct values than the index type can address
+    #[error("Palette of {len} values doesn't fit into the index type")]
+    PaletteOverflow { len: usize },
+    /// A range boundary couldn't be converted to u64, e.g. because it's negative
+    #[error("Invalid position: {0}")]
+    InvalidPosition(String),
+}
+
+impl From<BuildError> for io::Error {
+    fn from(value: BuildError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, value)
+    }
+}
+
+/// Encodes the distance between the end of the previous range and `start`
+pub(crate) fn checked_gap<T: TryFrom<u64>>(previous_end: u64, start: u64) -> Result<T, BuildError> {
+    if start <= previous_end {
+        return Err(BuildError::Overlap {
+            start,
+            previous_end,
+        });
+    }
+    let gap = start - previous_end;
+    T::try_from(gap).map_err(|_| BuildError::GapOverflow { gap })
+}
+
+/// Like `checked_gap`, but for the offset of the first range, which may be 0
+pub(crate) fn checked_offset<T: TryFrom<u64>>(start: u64) -> Result<T, BuildError> {
+    T::try_from(start).map_err(|_| BuildError::GapOverflow { gap: start })
+}
+
+pub(crate) fn checked_len<T: TryFrom<u64>>(start: u64, end: u64) -> Result<T, BuildError> {
+    if end <= start {
+        return Err(BuildError::EmptyRange { start, end });
+    }
+    let len = end - start;
+    T::try_from(len).map_err(|_| BuildError::LengthOverflow { len })
+}
+
+/// Fails, if ranges exceed the pixels of the ROI
+pub(crate) fn check_bounds(end: u64, bounds: Rect<u32>) -> Result<(), BuildError> {
+    let size = u64::from(bounds.width.get()) * u64::from(bounds.height.get());
+    if end > size {
+        return Err(BuildError::OutOfBounds { end, size });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_errors() {
+        assert_eq!(
+            Err(BuildError::EmptyRange { start: 5, end: 5 }),
+            checked_len::<u8>(5, 5)
+        );
+        assert_eq!(
+            Err(BuildError::LengthOverflow { len: 256 }),
+            checked_len::<u8>(0, 256)
+        );
+        assert_eq!(
+            Err(BuildError::Overlap {
+                start: 3,
+                previous_end: 3
+            }),
+            checked_gap::<u8>(3, 3)
+        );
+        assert_eq!(
+            Err(BuildError::GapOverflow { gap: 300 }),
+            checked_gap::<u8>(0, 300)
+        );
+        assert_eq!(Ok(0u8), checked_offset(0));
+    }
+}
@@ -6,9 +6,11 @@
 mod assert_sorted_iter;
 #[cfg(feature = "async-io")]
 mod async_io;
+mod build_error;
 mod create_range;
 mod map;
 mod non_zero;
+mod orientation;
 mod rect;
 mod set;
 mod span;
@@ -21,6 +23,7 @@ use std::num::NonZero;
 pub use assert_sorted_iter::*;
 #[cfg(feature = "async-io")]
 pub use async_io::*;
+pub use build_error::BuildError;
 pub use create_range::*;
 pub use map::*;
 pub use non_zero::*;
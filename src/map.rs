@@ -7,27 +7,46 @@ use std::{
 use num_traits::Zero;
 
 use crate::{
-    CreateRange, ImageDimension, NonZeroRange, Rect, SignedNonZeroable, SortedRangesIter,
-    UncheckedCast,
+    BuildError, CreateRange, ImageDimension, IntoRoiIterator, NonZeroRange, Rect,
+    SignedNonZeroable, SortedRangesIter, UncheckedCast, WithRoi,
+    build_error::{checked_gap, checked_len, checked_offset},
 };
 
+mod affine_transform;
+#[cfg(feature = "rkyv")]
+mod archived;
+mod interned;
 mod iter;
+mod iter_global;
+mod labels;
 mod map_inplace;
 mod offsets_iter;
+mod orientation;
+mod painter;
+#[cfg(feature = "serde")]
+mod serde_impl;
 
+pub use interned::*;
 pub use iter::*;
+pub use iter_global::*;
 pub use map_inplace::*;
 pub use offsets_iter::*;
+pub use painter::*;
+#[cfg(feature = "serde")]
+pub use serde_impl::serde_map_ranges;
 
 /// Represents areas on images. It's designed to efficiently support various image sizes.
-/// Both, TIncluded and TExcluded are expected to always be > 0. Use non-zero signed types
-/// Included represents the number of pixels to include, excluded encodes the gap between two included ranges
+/// Included represents the number of pixels to include and is always > 0, excluded encodes the gap between two included ranges.
+/// Gaps are > 0, except for the offset of the first range and between touching ranges of different meta
 ///
 /// Included.len() = excluded.len()
 ///
 /// Meta is expected to be indexable for each included range
 #[derive(Clone, Eq, PartialEq)]
-#[cfg_attr(feature = "rkyv", derive(rkyv::Archive))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
 pub struct SortedRangesMap<TIncluded, TExcluded, TMeta> {
     included: Vec<TIncluded>,
     excluded: Vec<TExcluded>,
@@ -63,13 +82,21 @@ impl<TIncluded: UncheckedCast<u64>, TExcluded: UncheckedCast<u64>, TMeta: Debug>
 type CopiedSliceIter<'a, T> = std::iter::Copied<std::slice::Iter<'a, T>>;
 
 impl<TIncluded, TExcluded, TMeta> SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>> {
+    /// Map without any range
+    pub fn empty(bounds: Rect<u32>) -> Self {
+        Self {
+            included: Vec::new(),
+            excluded: Vec::new(),
+            meta: Vec::new(),
+            bounds,
+        }
+    }
+
     pub fn new<TRange>(r: NonZeroRange<TRange>, meta: TMeta, bounds: Rect<u32>) -> Self
     where
         TRange:
             UncheckedCast<TIncluded> + UncheckedCast<TExcluded> + std::ops::Sub<Output = TRange>,
     {
-        assert!(bounds.x == 0);
-        assert!(bounds.y == 0);
         Self {
             included: vec![r.len().cast_unchecked()],
             excluded: vec![r.start.cast_unchecked()],
@@ -77,54 +104,52 @@ impl<TIncluded, TExcluded, TMeta> SortedRangesMap<TIncluded, TExcluded, Vec<TMet
             bounds,
         }
     }
+    /// Collects ordered ranges. Touching ranges are accepted, if their meta differs, so maps
+    /// returned by e.g. `iter_owned` can be rebuilt
     pub fn try_from_ordered_iter<TRange>(
         iter: impl IntoIterator<Item = (Range<TRange>, TMeta), IntoIter: ImageDimension>,
-    ) -> Result<Self, String>
+    ) -> Result<Self, BuildError>
     where
         TRange: Into<u64>,
         TIncluded: TryFrom<u64, Error: Display>,
         TExcluded: TryFrom<u64, Error: Display>,
+        TMeta: PartialEq,
     {
         let iter = iter.into_iter();
         let bounds = iter.bounds();
-        assert!(bounds.x == 0);
-        assert!(bounds.y == 0);
-        fn create_checked<T: TryFrom<u64, Error: Display>>(
-            start: u64,
-            end: u64,
-        ) -> Result<T, String> {
-            if end <= start {
-                return Err(format!("{} must be > {}", end, start));
-            }
-            T::try_from(end - start).map_err(|e| e.to_string())
-        }
-
-        let mut iter = iter.map(|(range, meta)| {
-            let start = range.start.into();
-            let end = range.end.into();
-            create_checked::<TIncluded>(start, end).map(|x| (start..end, x, meta))
-        });
-
-        let Some((first_range, first_len, first_meta)) = iter.next().transpose()? else {
-            return Err("Requires at least one item".into());
-        };
-        let initial_offset = TExcluded::try_from(first_range.start).map_err(|e| e.to_string())?;
-
-        let mut included = Vec::<TIncluded>::with_capacity(iter.size_hint().0 + 1);
-        let mut excluded = Vec::<TExcluded>::with_capacity(iter.size_hint().0 + 1);
-        let mut meta = Vec::<TMeta>::with_capacity(iter.size_hint().0 + 1);
-
-        included.push(first_len);
-        excluded.push(initial_offset);
-        meta.push(first_meta);
+        Self::try_from_ordered_iter_roi(iter, bounds)
+    }
 
-        let mut cur_pos = first_range.end;
-        for x in iter {
-            let (next_range, next_len, next_meta) = x?;
-            excluded.push(create_checked(cur_pos, next_range.start)?);
-            included.push(next_len);
+    /// Like `try_from_ordered_iter`, but ranges are relative to `bounds`, which may be
+    /// offset within the parent image
+    pub fn try_from_ordered_iter_roi<TRange>(
+        iter: impl IntoIterator<Item = (Range<TRange>, TMeta)>,
+        bounds: Rect<u32>,
+    ) -> Result<Self, BuildError>
+    where
+        TRange: Into<u64>,
+        TIncluded: TryFrom<u64, Error: Display>,
+        TExcluded: TryFrom<u64, Error: Display>,
+        TMeta: PartialEq,
+    {
+        let iter = iter.into_iter();
+        let size_hint = iter.size_hint().0;
+        let mut included = Vec::<TIncluded>::with_capacity(size_hint);
+        let mut excluded = Vec::<TExcluded>::with_capacity(size_hint);
+        let mut meta = Vec::<TMeta>::with_capacity(size_hint);
+
+        let mut cur_pos = 0;
+        for (range, next_meta) in iter {
+            let (start, end) = (range.start.into(), range.end.into());
+            included.push(checked_len(start, end)?);
+            excluded.push(match meta.last() {
+                None => checked_offset(start)?,
+                // Touching ranges stay separate, if the meta differs
+                Some(last) if start == cur_pos && *last != next_meta => checked_offset(0)?,
+                Some(_) => checked_gap(cur_pos, start)?,
+            });
             meta.push(next_meta);
-            cur_pos = next_range.end;
+            cur_pos = end;
         }
 
         Ok(Self {
@@ -134,6 +159,37 @@ impl<TIncluded, TExcluded, TMeta> SortedRangesMap<TIncluded, TExcluded, Vec<TMet
             bounds,
         })
     }
+
+    /// Collects ranges produced within this crate. Unlike `try_from_ordered_iter`, the meta of
+    /// touching ranges isn't compared
+    pub(crate) fn from_ordered_ranges(
+        ranges: impl IntoIterator<Item = (Range<u64>, TMeta)>,
+        bounds: Rect<u32>,
+    ) -> Result<Self, BuildError>
+    where
+        TIncluded: TryFrom<u64>,
+        TExcluded: TryFrom<u64>,
+    {
+        let ranges = ranges.into_iter();
+        let size_hint = ranges.size_hint().0;
+        let mut included = Vec::with_capacity(size_hint);
+        let mut excluded = Vec::with_capacity(size_hint);
+        let mut meta = Vec::with_capacity(size_hint);
+        let mut cur_pos = 0;
+        for (range, m) in ranges {
+            debug_assert!(cur_pos <= range.start, "Ranges must be ordered");
+            excluded.push(checked_offset(range.start - cur_pos)?);
+            included.push(checked_len(range.start, range.end)?);
+            meta.push(m);
+            cur_pos = range.end;
+        }
+        Ok(Self {
+            included,
+            excluded,
+            meta,
+            bounds,
+        })
+    }
     pub fn iter<T: CreateRange<Item: Zero>>(
         &self,
     ) -> SortedRangesMapIter<
@@ -154,14 +210,17 @@ impl<TIncluded, TExcluded, TMeta> SortedRangesMap<TIncluded, TExcluded, Vec<TMet
         )
     }
 
-    #[allow(clippy::len_without_is_empty, reason = "is_empty would always be true")]
     pub fn len(&self) -> usize {
         self.included.len()
     }
 
-    pub fn len_nonzero(&self) -> NonZero<usize> {
+    /// Returns the number of ranges or None, if the map is empty
+    pub fn len_nonzero(&self) -> Option<NonZero<usize>> {
         NonZero::new(self.included.len())
-            .expect("Constructors make sure, there is always at least one Range")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.included.is_empty()
     }
 
     pub fn iter_owned<T: CreateRange<Item: Zero>>(
@@ -184,6 +243,54 @@ impl<TIncluded, TExcluded, TMeta> SortedRangesMap<TIncluded, TExcluded, Vec<TMet
         )
     }
 
+    /// Ranges with meta in the coordinates of a parent image with `width` columns.
+    /// See `SortedRangesMapIterGlobal`
+    pub fn iter_global_with<T: CreateRange>(
+        &self,
+        width: NonZero<u32>,
+    ) -> SortedRangesMapIterGlobal<
+        CopiedSliceIter<'_, TIncluded>,
+        CopiedSliceIter<'_, TExcluded>,
+        std::slice::Iter<'_, TMeta>,
+        T,
+    >
+    where
+        TIncluded: UncheckedCast<T::Item>,
+        TExcluded: UncheckedCast<T::Item>,
+        T::Item: Default,
+    {
+        SortedRangesMapIterGlobal::new(
+            self.included.iter().copied(),
+            self.excluded.iter().copied(),
+            self.meta.iter(),
+            self.bounds,
+            width,
+        )
+    }
+
+    pub fn iter_global_owned_with<T: CreateRange>(
+        self,
+        width: NonZero<u32>,
+    ) -> SortedRangesMapIterGlobal<
+        std::vec::IntoIter<TIncluded>,
+        std::vec::IntoIter<TExcluded>,
+        std::vec::IntoIter<TMeta>,
+        T,
+    >
+    where
+        TIncluded: UncheckedCast<T::Item>,
+        TExcluded: UncheckedCast<T::Item>,
+        T::Item: Default,
+    {
+        SortedRangesMapIterGlobal::new(
+            self.included.into_iter(),
+            self.excluded.into_iter(),
+            self.meta.into_iter(),
+            self.bounds,
+            width,
+        )
+    }
+
     pub fn ranges<T: CreateRange>(
         &self,
     ) -> SortedRangesIter<
@@ -253,6 +360,44 @@ impl<TIncluded: UncheckedCast<u64>, TExcluded: UncheckedCast<u64>, TMeta> IntoIt
     }
 }
 
+impl<TIncluded, TExcluded, TMeta, T> IntoRoiIterator<T>
+    for SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>
+where
+    T: CreateRange<Item: Default + Copy + SignedNonZeroable + std::ops::Add<Output = T::Item>>,
+    TIncluded: UncheckedCast<T::Item>,
+    TExcluded: UncheckedCast<T::Item>,
+{
+    type IntoIter =
+        WithRoi<SortedRangesIter<std::vec::IntoIter<TIncluded>, std::vec::IntoIter<TExcluded>, T>>;
+
+    /// Ranges without their meta
+    fn into_roi_iterator(self) -> Self::IntoIter {
+        let bounds = self.bounds;
+        WithRoi::new(self.ranges_owned(), bounds)
+    }
+}
+
+impl<'a, TIncluded, TExcluded, TMeta, T> IntoRoiIterator<T>
+    for &'a SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>
+where
+    T: CreateRange<Item: Default + Copy + SignedNonZeroable + std::ops::Add<Output = T::Item>>,
+    TIncluded: UncheckedCast<T::Item>,
+    TExcluded: UncheckedCast<T::Item>,
+{
+    type IntoIter = WithRoi<
+        SortedRangesIter<
+            std::iter::Copied<std::slice::Iter<'a, TIncluded>>,
+            std::iter::Copied<std::slice::Iter<'a, TExcluded>>,
+            T,
+        >,
+    >;
+
+    /// Ranges without their meta
+    fn into_roi_iterator(self) -> Self::IntoIter {
+        WithRoi::new(self.ranges(), self.bounds)
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
 pub struct MetaRange<TRange, TMeta> {
     pub range: TRange,
@@ -368,12 +513,10 @@ mod tests {
             .unwrap();
 
             let b_iter = b.iter_owned::<RangeInclusive<u64>>();
-            let a = a
-                .map_inplace(|a_iter| {
-                    range_set_blaze_0_5::SortedDisjointMap::union(b_iter, a_iter)
-                        .map(|(r, m)| (*r.start()..=(*r.end()), m))
-                })
-                .unwrap();
+            let a = a.map_inplace(|a_iter| {
+                range_set_blaze_0_5::SortedDisjointMap::union(b_iter, a_iter)
+                    .map(|(r, m)| (*r.start()..=(*r.end()), m))
+            });
 
             assert_eq!(
                 vec![
@@ -456,7 +599,7 @@ mod tests {
             [(10u32..20, "first"), (276..280, "second")].with_roi(test_bounds()),
         )
         .unwrap_err();
-        assert!(error.contains("out of range"), "{error}");
+        assert_eq!(BuildError::GapOverflow { gap: 256 }, error);
     }
 
     #[test]
@@ -465,7 +608,7 @@ mod tests {
             [(10u32..280, "first")].with_roi(test_bounds()),
         )
         .unwrap_err();
-        assert!(error.contains("out of range"), "{error}");
+        assert_eq!(BuildError::LengthOverflow { len: 270 }, error);
     }
     #[test]
     fn zero_ranges_cause_error() {
@@ -473,7 +616,7 @@ mod tests {
             [(10u32..10, "first")].with_roi(test_bounds()),
         )
         .unwrap_err();
-        assert!(error.contains("> 10"), "{error}");
+        assert_eq!(BuildError::EmptyRange { start: 10, end: 10 }, error);
     }
 
     #[test]
@@ -482,7 +625,47 @@ mod tests {
             [(10u32..12, "first"), (11..12, "second")].with_roi(test_bounds()),
         )
         .unwrap_err();
-        assert!(error.contains("> 12"), "{error}");
+        assert_eq!(
+            BuildError::Overlap {
+                start: 11,
+                previous_end: 12
+            },
+            error
+        );
+    }
+
+    #[test]
+    fn touching_ranges_with_different_meta_roundtrip() {
+        let ranges = [(10u32..12, "a"), (12..15, "b"), (20..22, "a")];
+        let encoded = SortedRangesMap::<u8, u8, Vec<_>>::try_from_ordered_iter(
+            ranges.clone().with_roi(test_bounds()),
+        )
+        .unwrap();
+        let collected: Vec<_> = encoded.iter_owned::<Range<u32>>().collect();
+        assert_eq!(ranges.to_vec(), collected);
+        let rebuilt = SortedRangesMap::<u8, u8, Vec<_>>::try_from_ordered_iter(
+            collected.with_roi(test_bounds()),
+        )
+        .unwrap();
+        assert_eq!(
+            ranges.to_vec(),
+            rebuilt.iter_owned::<Range<u32>>().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn touching_ranges_with_equal_meta_cause_error() {
+        let error = SortedRangesMap::<u8, u8, _>::try_from_ordered_iter(
+            [(10u32..12, "first"), (12..14, "first")].with_roi(test_bounds()),
+        )
+        .unwrap_err();
+        assert_eq!(
+            BuildError::Overlap {
+                start: 12,
+                previous_end: 12
+            },
+            error
+        );
     }
 
     #[test]
@@ -492,14 +675,12 @@ mod tests {
         )
         .unwrap();
 
-        let a = a
-            .map_inplace(|iter| {
-                iter.map(|(x, m)| {
-                    let (start, end) = x.into_inner();
-                    ((start + 5)..=(end + 5), m)
-                })
+        let a = a.map_inplace(|iter| {
+            iter.map(|(x, m)| {
+                let (start, end) = x.into_inner();
+                ((start + 5)..=(end + 5), m)
             })
-            .unwrap();
+        });
 
         assert_eq!(
             vec![(15u64..=19, "a1"), (35..=39, "a2")],
@@ -520,14 +701,12 @@ mod tests {
         )
         .unwrap();
 
-        let a = a
-            .map_inplace(|iter| {
-                iter.flat_map(|(x, m)| {
-                    let with_offset = (*x.start() + 10)..=(*x.end() + 10);
-                    [(x, m.clone()), (with_offset, format!("{}_offset", m))]
-                })
+        let a = a.map_inplace(|iter| {
+            iter.flat_map(|(x, m)| {
+                let with_offset = (*x.start() + 10)..=(*x.end() + 10);
+                [(x, m.clone()), (with_offset, format!("{}_offset", m))]
             })
-            .unwrap();
+        });
 
         assert_eq!(
             vec![
@@ -543,7 +722,7 @@ mod tests {
     }
 
     #[test]
-    fn split_returns_none_when_empty() {
+    fn split_returns_empty_map() {
         let a = SortedRangesMap::<u8, u8, Vec<String>>::try_from_ordered_iter(
             [(10u32..15, "test".to_string())].with_roi(test_bounds()),
         )
@@ -551,6 +730,42 @@ mod tests {
 
         let result = a.map_inplace(|_| std::iter::empty());
 
-        assert!(result.is_none());
+        assert!(result.is_empty());
+        assert_eq!(test_bounds(), result.bounds());
+    }
+
+    #[test]
+    fn roi_map_clipped_with_meta() {
+        let roi = Rect::new(20, 10, NonZero::new(10).unwrap(), NonZero::new(10).unwrap());
+        let map = SortedRangesMap::<u8, u8, Vec<&str>>::try_from_ordered_iter(
+            [(5u32..25, "left"), (27..29, "right"), (35..45, "center")].with_roi(roi),
+        )
+        .unwrap();
+        assert_eq!(roi, map.bounds());
+
+        // Columns 4..8 of the rows 1..4 within the ROI
+        let sub = Rect::new(4, 1, NonZero::new(4).unwrap(), NonZero::new(3).unwrap());
+        let clipped = map
+            .clone()
+            .iter_owned::<Range<u32>>()
+            .with_roi(map.bounds())
+            .try_clip_2d_meta(sub)
+            .unwrap();
+        let clipped = SortedRangesMap::<u8, u8, Vec<&str>>::try_from_ordered_iter(clipped).unwrap();
+        assert_eq!(sub, clipped.bounds());
+        assert_eq!(
+            vec![(0..5, "left"), (7..8, "right"), (9..12, "center")],
+            clipped.iter_owned::<Range<u64>>().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn empty_iter_creates_empty_map() {
+        let empty = SortedRangesMap::<u8, u8, Vec<String>>::try_from_ordered_iter(
+            std::iter::empty::<(Range<u32>, String)>().with_roi(test_bounds()),
+        )
+        .unwrap();
+        assert_eq!(SortedRangesMap::empty(test_bounds()), empty);
+        assert_eq!(0, empty.iter::<Range<u64>>().count());
     }
 }
@@ -0,0 +1,90 @@
+use std::{fmt::Display, ops::Range};
+
+use nalgebra::Matrix3;
+
+use crate::{BuildError, ImaskSet, Rect, SortedRangesMap, UncheckedCast};
+
+impl<TIncluded, TExcluded, TMeta> SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TMeta: Clone + PartialEq,
+{
+    /// Like `SortedRanges::transform`, but each output pixel keeps the meta of the range it originates from.
+    /// If multiple ranges are mapped onto the same pixel, the earlier range wins
+    pub fn transform(
+        &self,
+        matrix: &Matrix3<f64>,
+        output_bounds: Rect<u32>,
+    ) -> Result<Self, BuildError> {
+        let mut transformed = self
+            .iter::<Range<u64>>()
+            .map(|(r, _)| r)
+            .with_roi(self.bounds)
+            .affine_transform(matrix, output_bounds);
+
+        let mut ranges: Vec<(Range<u64>, &TMeta)> = Vec::new();
+        while let Some((range, source)) = transformed.next_with_source() {
+            let range = u64::from(range.start)..u64::from(range.end);
+            let meta = &self.meta[source as usize];
+            match ranges.last_mut() {
+                Some((last, last_meta)) if last.end == range.start && *last_meta == meta => {
+                    last.end = range.end
+                }
+                _ => ranges.push((range, meta)),
+            }
+        }
+
+        Self::from_ordered_ranges(
+            ranges.into_iter().map(|(r, m)| (r, m.clone())),
+            output_bounds,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use nalgebra::Vector2;
+
+    use crate::{ImageDimension, ImaskSet};
+
+    use super::*;
+
+    /// ```text
+    /// aa.b
+    /// ....
+    /// ```
+    fn sample() -> SortedRangesMap<u8, u8, Vec<char>> {
+        SortedRangesMap::try_from_ordered_iter(
+            [(0u32..2, 'a'), (3..4, 'b')]
+                .with_bounds(NonZero::new(4).unwrap(), NonZero::new(2).unwrap()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn translation_keeps_meta() {
+        let output = Rect::new(0, 0, NonZero::new(6).unwrap(), NonZero::new(2).unwrap());
+        let shift = Matrix3::new_translation(&Vector2::new(1.0, 1.0));
+        let shifted = sample().transform(&shift, output).unwrap();
+        assert_eq!(output, shifted.bounds());
+        assert_eq!(
+            vec![(7u64..9, &'a'), (10..11, &'b')],
+            shifted.iter::<Range<u64>>().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn touching_ranges_with_different_meta_stay_separate() {
+        // Squeeze the gap between 'a' and 'b' by moving 'b' one pixel to the left
+        let output = Rect::new(0, 0, NonZero::new(4).unwrap(), NonZero::new(1).unwrap());
+        let scale = Matrix3::new_nonuniform_scaling(&Vector2::new(0.75, 1.0));
+        let scaled = sample().transform(&scale, output).unwrap();
+        assert_eq!(
+            vec![(0u64..2, &'a'), (2..3, &'b')],
+            scaled.iter::<Range<u64>>().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use std::num::NonZero;
+
+use num_traits::Zero;
+use rkyv::Archive;
+
+use crate::{
+    ArchivedSortedRangesMap, ArchivedValuesIter, CreateRange, ImageDimension, Rect,
+    SignedNonZeroable, SortedRangesIter, SortedRangesMapIter, UncheckedCast,
+};
+
+/// Same API as `SortedRangesMap`, but works directly on the archive without deserializing.
+/// Meta is returned in its archived form
+impl<TIncluded: Archive, TExcluded: Archive, TMeta: Archive>
+    ArchivedSortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>
+{
+    pub fn len(&self) -> usize {
+        self.included.len()
+    }
+
+    /// Returns the number of ranges or None, if the map is empty
+    pub fn len_nonzero(&self) -> Option<NonZero<usize>> {
+        NonZero::new(self.included.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.included.is_empty()
+    }
+
+    pub fn iter<T: CreateRange<Item: Zero>>(
+        &self,
+    ) -> SortedRangesMapIter<
+        ArchivedValuesIter<'_, TIncluded>,
+        ArchivedValuesIter<'_, TExcluded>,
+        std::slice::Iter<'_, TMeta::Archived>,
+        T,
+    >
+    where
+        TIncluded: UncheckedCast<T::Item> + From<TIncluded::Archived>,
+        TExcluded: UncheckedCast<T::Item> + From<TExcluded::Archived>,
+        TIncluded::Archived: Copy,
+        TExcluded::Archived: Copy,
+    {
+        SortedRangesMapIter::new(
+            ArchivedValuesIter::new(&self.included),
+            ArchivedValuesIter::new(&self.excluded),
+            self.meta.iter(),
+            Zero::zero(),
+        )
+    }
+
+    pub fn ranges<T: CreateRange>(
+        &self,
+    ) -> SortedRangesIter<ArchivedValuesIter<'_, TIncluded>, ArchivedValuesIter<'_, TExcluded>, T>
+    where
+        TIncluded: UncheckedCast<T::Item> + From<TIncluded::Archived>,
+        TExcluded: UncheckedCast<T::Item> + From<TExcluded::Archived>,
+        TIncluded::Archived: Copy,
+        TExcluded::Archived: Copy,
+        T::Item: Default + Copy + SignedNonZeroable + std::ops::Add<Output = T::Item>,
+    {
+        let bounds = self.bounds();
+        SortedRangesIter::new(
+            ArchivedValuesIter::new(&self.included),
+            ArchivedValuesIter::new(&self.excluded),
+            T::Item::default(),
+            bounds.width,
+            bounds.height,
+        )
+    }
+}
+
+impl<TIncluded: Archive, TExcluded: Archive, TMeta: Archive> ImageDimension
+    for ArchivedSortedRangesMap<TIncluded, TExcluded, TMeta>
+{
+    fn width(&self) -> NonZero<u32> {
+        self.bounds.width.into()
+    }
+
+    fn bounds(&self) -> Rect<u32> {
+        self.bounds.to_native()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use crate::{ImaskSet, SortedRangesMap};
+
+    use super::*;
+
+    #[test]
+    fn iterate_archive_without_deserializing() {
+        let size = NonZero::new(100).unwrap();
+        let map = SortedRangesMap::<u8, u16, Vec<u32>>::try_from_ordered_iter(
+            [(10u32..20, 1), (300..400, 2)].with_bounds(size, size),
+        )
+        .unwrap();
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&map).unwrap();
+        let archived =
+            rkyv::access::<ArchivedSortedRangesMap<u8, u16, Vec<u32>>, rkyv::rancor::Error>(&bytes)
+                .unwrap();
+
+        assert_eq!(map.bounds(), archived.bounds());
+        assert_eq!(2, archived.len());
+        assert_eq!(
+            vec![(10u64..20, 1u32), (300..400, 2)],
+            archived
+                .iter::<Range<u64>>()
+                .map(|(r, m)| (r, m.to_native()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            map.ranges::<Range<u64>>().collect::<Vec<_>>(),
+            archived.ranges::<Range<u64>>().collect::<Vec<_>>()
+        );
+    }
+}
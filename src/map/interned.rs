@@ -0,0 +1,216 @@
+use std::{collections::HashMap, hash::Hash, iter::FusedIterator, num::NonZero};
+
+use num_traits::Zero;
+
+use super::CopiedSliceIter;
+use crate::{BuildError, CreateRange, SortedRangesMap, SortedRangesMapIter, UncheckedCast};
+
+/// Stores each distinct meta once. Ranges refer to it by a compact index, e.g. `u8` for up to
+/// 256 class names. The palette is ordered by first occurrence, so interning equal maps
+/// results in equal palettes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternedMeta<TMeta, TIndex> {
+    palette: Vec<TMeta>,
+    indices: Vec<TIndex>,
+}
+
+impl<TMeta, TIndex> InternedMeta<TMeta, TIndex> {
+    pub fn palette(&self) -> &[TMeta] {
+        &self.palette
+    }
+
+    /// Index into the palette for each range
+    pub fn indices(&self) -> &[TIndex] {
+        &self.indices
+    }
+}
+
+impl<TIncluded, TExcluded, TMeta> SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>> {
+    /// Moves the meta into a palette. Use `normalize` first to merge touching ranges with equal meta
+    /// ```
+    /// use std::{num::NonZero, ops::Range};
+    /// use imask::{ImaskSet, SortedRangesMap};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let size = NonZero::new(10).unwrap();
+    /// let map = SortedRangesMap::<u16, u16, Vec<String>>::try_from_ordered_iter(
+    ///     [(0u32..5, "car".to_string()), (7..9, "tree".into()), (20..25, "car".into())].with_bounds(size, size),
+    /// )?;
+    /// let interned = map.clone().intern::<u8>()?;
+    /// assert_eq!(["car", "tree"], interned.palette());
+    /// assert_eq!(vec![(20..25, "car")], interned.iter::<Range<u64>>().skip(2).map(|(r, m)| (r, m.as_str())).collect::<Vec<_>>());
+    /// assert_eq!(map, interned.into_expanded());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn intern<TIndex: TryFrom<usize>>(
+        self,
+    ) -> Result<SortedRangesMap<TIncluded, TExcluded, InternedMeta<TMeta, TIndex>>, BuildError>
+    where
+        TMeta: Hash + Eq + Clone,
+    {
+        let mut lookup = HashMap::new();
+        let mut palette = Vec::new();
+        let mut indices = Vec::with_capacity(self.meta.len());
+        for meta in self.meta {
+            let index = *lookup.entry(meta).or_insert_with_key(|meta| {
+                palette.push(meta.clone());
+                palette.len() - 1
+            });
+            let index = TIndex::try_from(index)
+                .map_err(|_| BuildError::PaletteOverflow { len: palette.len() })?;
+            indices.push(index);
+        }
+        Ok(SortedRangesMap {
+            included: self.included,
+            excluded: self.excluded,
+            meta: InternedMeta { palette, indices },
+            bounds: self.bounds,
+        })
+    }
+}
+
+impl<TIncluded, TExcluded, TMeta, TIndex>
+    SortedRangesMap<TIncluded, TExcluded, InternedMeta<TMeta, TIndex>>
+{
+    pub fn len(&self) -> usize {
+        self.included.len()
+    }
+
+    /// Returns the number of ranges or None, if the map is empty
+    pub fn len_nonzero(&self) -> Option<NonZero<usize>> {
+        NonZero::new(self.included.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.included.is_empty()
+    }
+
+    pub fn palette(&self) -> &[TMeta] {
+        &self.meta.palette
+    }
+
+    pub fn interned_meta(&self) -> &InternedMeta<TMeta, TIndex> {
+        &self.meta
+    }
+
+    pub fn iter<T: CreateRange<Item: Zero>>(
+        &self,
+    ) -> SortedRangesMapIter<
+        CopiedSliceIter<'_, TIncluded>,
+        CopiedSliceIter<'_, TExcluded>,
+        InternedMetaIter<'_, TMeta, TIndex>,
+        T,
+    >
+    where
+        TIncluded: UncheckedCast<T::Item>,
+        TExcluded: UncheckedCast<T::Item>,
+    {
+        SortedRangesMapIter::new(
+            self.included.iter().copied(),
+            self.excluded.iter().copied(),
+            InternedMetaIter {
+                palette: &self.meta.palette,
+                indices: self.meta.indices.iter(),
+            },
+            Zero::zero(),
+        )
+    }
+
+    /// Stores a clone of the meta for each range again
+    pub fn into_expanded(self) -> SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>
+    where
+        TMeta: Clone,
+        TIndex: UncheckedCast<usize>,
+    {
+        let InternedMeta { palette, indices } = self.meta;
+        SortedRangesMap {
+            included: self.included,
+            excluded: self.excluded,
+            meta: indices
+                .into_iter()
+                .map(|i| palette[i.cast_unchecked()].clone())
+                .collect(),
+            bounds: self.bounds,
+        }
+    }
+}
+
+/// Resolves the palette index of each range
+#[derive(Clone)]
+pub struct InternedMetaIter<'a, TMeta, TIndex> {
+    palette: &'a [TMeta],
+    indices: std::slice::Iter<'a, TIndex>,
+}
+
+impl<'a, TMeta, TIndex: UncheckedCast<usize>> Iterator for InternedMetaIter<'a, TMeta, TIndex> {
+    type Item = &'a TMeta;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = self.indices.next()?;
+        Some(&self.palette[index.cast_unchecked()])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl<TMeta, TIndex: UncheckedCast<usize>> FusedIterator for InternedMetaIter<'_, TMeta, TIndex> {}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use crate::Rect;
+
+    use super::*;
+
+    fn map(labels: &[&'static str]) -> SortedRangesMap<u8, u8, Vec<&'static str>> {
+        let ranges = (0u64..).step_by(2).map(|start| start..start + 2);
+        SortedRangesMap::from_ordered_ranges(
+            ranges.zip(labels.iter().copied()),
+            Rect::new(0, 0, NonZero::new(100).unwrap(), NonZero::<u32>::MIN),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn normalized_maps_intern_equally() {
+        // Same area, but split differently
+        let split = map(&["a", "a", "b", "a"]);
+        let merged = SortedRangesMap::<u8, u8, Vec<_>>::from_ordered_ranges(
+            [(0..4, "a"), (4..6, "b"), (6..8, "a")],
+            split.bounds,
+        )
+        .unwrap();
+        assert_ne!(split, merged);
+        let split = split.normalize().unwrap();
+        assert_eq!(split, merged);
+        let interned = split.intern::<u8>().unwrap();
+        assert!(interned == merged.intern::<u8>().unwrap());
+        assert_eq!(&[0u8, 1, 0], interned.interned_meta().indices());
+        assert_eq!(
+            vec![(0..4, &"a"), (4..6, &"b"), (6..8, &"a")],
+            interned.iter::<Range<u64>>().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn palette_overflow() {
+        let labels = ["a", "b", "c"];
+        let many = map(&labels.repeat(2));
+        assert_eq!(3, many.clone().intern::<u8>().unwrap().palette().len());
+        let names = (0..300).map(|i| i.to_string()).collect::<Vec<_>>();
+        let ranges = (0u64..300).map(|i| i * 2..i * 2 + 1);
+        let wide = SortedRangesMap::<u16, u16, Vec<String>>::from_ordered_ranges(
+            ranges.zip(names),
+            Rect::new(0, 0, NonZero::new(1000).unwrap(), NonZero::<u32>::MIN),
+        )
+        .unwrap();
+        assert!(matches!(
+            wide.intern::<u8>(),
+            Err(BuildError::PaletteOverflow { len: 257 })
+        ));
+    }
+}
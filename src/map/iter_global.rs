@@ -0,0 +1,205 @@
+use std::{
+    cmp::min,
+    iter::FusedIterator,
+    num::NonZeroU32,
+    ops::{Add, Div, Mul, Rem, Sub},
+};
+
+use crate::{CreateRange, ImageDimension, MetaRange, Rect, SignedNonZeroable, UncheckedCast};
+
+/// Ranges of a map placed into a parent image of `width` columns. The ROI offset of the map is
+/// applied and columns beyond `width` are cut. Ranges are split at row ends, unless the rows stay
+/// contiguous, i.e. the ROI starts at column 0 and is at least as wide as the parent image.
+/// Pieces of a range share its meta, ranges of different meta are never merged
+pub struct SortedRangesMapIterGlobal<I, E, M: Iterator, T: CreateRange> {
+    included: I,
+    excluded: E,
+    meta: M,
+    /// Position within the ROI of the part not yielded yet
+    pos: T::Item,
+    remaining: T::Item,
+    current: Option<M::Item>,
+    roi: Rect<u32>,
+    width: NonZeroU32,
+}
+
+impl<I, E, M: Iterator, T: CreateRange> SortedRangesMapIterGlobal<I, E, M, T>
+where
+    T::Item: Default,
+{
+    pub(crate) fn new(
+        included: I,
+        excluded: E,
+        meta: M,
+        roi: Rect<u32>,
+        width: NonZeroU32,
+    ) -> Self {
+        Self {
+            included,
+            excluded,
+            meta,
+            pos: T::Item::default(),
+            remaining: T::Item::default(),
+            current: None,
+            roi,
+            width,
+        }
+    }
+}
+
+impl<I, E, M: Iterator, T: CreateRange> ImageDimension for SortedRangesMapIterGlobal<I, E, M, T> {
+    fn width(&self) -> NonZeroU32 {
+        self.width
+    }
+
+    fn bounds(&self) -> Rect<u32> {
+        Rect {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: NonZeroU32::new(self.roi.height.get() + self.roi.y).unwrap(),
+        }
+    }
+}
+
+impl<TI, TE, TM, TOut> Iterator for SortedRangesMapIterGlobal<TI, TE, TM, TOut>
+where
+    TI: Iterator<Item: UncheckedCast<TOut::Item>>,
+    TE: Iterator<Item: UncheckedCast<TOut::Item>>,
+    TM: Iterator<Item: Clone>,
+    TOut: CreateRange<
+        Item: Copy
+                  + Default
+                  + SignedNonZeroable
+                  + Add<Output = TOut::Item>
+                  + Sub<Output = TOut::Item>
+                  + Mul<Output = TOut::Item>
+                  + Div<Output = TOut::Item>
+                  + Rem<Output = TOut::Item>
+                  + Ord,
+    >,
+    u32: UncheckedCast<TOut::Item>,
+{
+    type Item = MetaRange<TOut, TM::Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let zero = TOut::Item::default();
+        let roi_x: TOut::Item = self.roi.x.cast_unchecked();
+        let roi_y: TOut::Item = self.roi.y.cast_unchecked();
+        let roi_width: TOut::Item = self.roi.width.get().cast_unchecked();
+        let width: TOut::Item = self.width.get().cast_unchecked();
+        let place = |p: TOut::Item, col: TOut::Item| {
+            (p / roi_width + roi_y) * width + min(col + roi_x, width)
+        };
+
+        loop {
+            if self.remaining > zero {
+                let col = self.pos % roi_width;
+                let take = min(self.remaining, roi_width - col);
+                let (s, e) = (place(self.pos, col), place(self.pos, col + take));
+                self.pos = self.pos + take;
+                self.remaining = self.remaining - take;
+                let meta = if self.remaining > zero {
+                    self.current.clone()
+                } else {
+                    self.current.take()
+                };
+                if s < e {
+                    return Some(MetaRange {
+                        range: TOut::new_debug_checked_zeroable(s, e),
+                        meta: meta.expect("Meta is kept until the range is done"),
+                    });
+                }
+                continue;
+            }
+
+            self.pos = self.pos + self.excluded.next()?.cast_unchecked();
+            let include: TOut::Item = self.included.next()?.cast_unchecked();
+            let meta = self.meta.next()?;
+            if self.roi.x == 0 && self.roi.width >= self.width {
+                // Rows are contiguous: Remap both ends
+                let end = self.pos + include;
+                let remap = |p: TOut::Item| place(p, p % roi_width);
+                let (s, e) = (remap(self.pos), remap(end));
+                self.pos = end;
+                if s < e {
+                    return Some(MetaRange {
+                        range: TOut::new_debug_checked_zeroable(s, e),
+                        meta,
+                    });
+                }
+            } else {
+                self.remaining = include;
+                self.current = Some(meta);
+            }
+        }
+    }
+}
+
+impl<TI, TE, TM: Iterator, TOut: CreateRange> FusedIterator
+    for SortedRangesMapIterGlobal<TI, TE, TM, TOut>
+where
+    Self: Iterator,
+{
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZero, ops::Range};
+
+    use crate::SortedRangesMap;
+
+    use super::*;
+
+    /// ROI (2, 1, 4x3)
+    /// ```text
+    /// aaaa
+    /// ..bb
+    /// bbcc
+    /// ```
+    fn map() -> SortedRangesMap<u8, u8, Vec<char>> {
+        let roi = Rect::new(2, 1, NonZero::new(4).unwrap(), NonZero::new(3).unwrap());
+        SortedRangesMap::from_ordered_ranges([(0..4, 'a'), (6..10, 'b'), (10..12, 'c')], roi)
+            .unwrap()
+    }
+
+    #[test]
+    fn ranges_are_placed_into_the_parent() {
+        let map = map();
+        let iter = map.iter_global_with::<Range<u64>>(NonZero::new(10).unwrap());
+        assert_eq!(
+            Rect::new(0, 0, NonZero::new(10).unwrap(), NonZero::new(4).unwrap()),
+            iter.bounds()
+        );
+        let global = iter.map(|m| (m.range, *m.meta)).collect::<Vec<_>>();
+        assert_eq!(
+            vec![(12..16, 'a'), (24..26, 'b'), (32..34, 'b'), (34..36, 'c')],
+            global
+        );
+        // Columns beyond the parent width are cut
+        let narrow = map
+            .iter_global_owned_with::<Range<u64>>(NonZero::new(5).unwrap())
+            .map(|m| (m.range, m.meta))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![(7..10, 'a'), (14..15, 'b'), (17..19, 'b'), (19..20, 'c')],
+            narrow
+        );
+    }
+
+    #[test]
+    fn contiguous_rows_are_not_split() {
+        let bounds = Rect::new(0, 1, NonZero::new(10).unwrap(), NonZero::new(3).unwrap());
+        let map = SortedRangesMap::<u8, u8, Vec<char>>::try_from_ordered_iter_roi(
+            [(5u32..20, 'a'), (21..23, 'b'), (25..27, 'c')],
+            bounds,
+        )
+        .unwrap();
+        let global = map
+            .iter_global_with::<Range<u64>>(NonZero::new(5).unwrap())
+            .map(|m| (m.range, *m.meta))
+            .collect::<Vec<_>>();
+        // The last range is right of the parent image
+        assert_eq!(vec![(10..15, 'a'), (16..18, 'b')], global);
+    }
+}
@@ -0,0 +1,185 @@
+use std::{collections::BTreeSet, fmt::Display, ops::Range};
+
+use crate::{BuildError, SortedRanges, SortedRangesMap, UncheckedCast};
+
+/// Per-label operations. Touching ranges, which end up with equal meta, are coalesced.
+/// Results keep the bounds of the map. Operations fail, if coalesced ranges or gaps of removed
+/// ranges don't fit into `TIncluded` or `TExcluded`
+/// ```
+/// use std::{num::NonZero, ops::Range};
+/// use imask::{ImaskSet, SortedRangesMap};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let size = NonZero::new(10).unwrap();
+/// let map = SortedRangesMap::<u16, u16, Vec<&str>>::try_from_ordered_iter(
+///     [(0u32..5, "cat"), (7..9, "dog"), (20..25, "cat")].with_bounds(size, size),
+/// )?;
+/// assert_eq!(vec![0u64..5, 20..25], map.extract::<u16, u16>(&"cat")?.iter_roi::<Range<u64>>().collect::<Vec<_>>());
+/// let animals = map.relabel(|_| "animal")?;
+/// assert_eq!(3, animals.len());
+/// # Ok(())
+/// # }
+/// ```
+impl<TIncluded, TExcluded, TMeta> SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64>,
+{
+    /// Distinct labels of the map
+    pub fn labels(&self) -> BTreeSet<&TMeta>
+    where
+        TMeta: Ord,
+    {
+        self.meta.iter().collect()
+    }
+
+    /// Ranges with meta equal to `label`
+    pub fn extract<TOutIncluded, TOutExcluded>(
+        &self,
+        label: &TMeta,
+    ) -> Result<SortedRanges<TOutIncluded, TOutExcluded>, BuildError>
+    where
+        TMeta: PartialEq,
+        TOutIncluded: TryFrom<u64, Error: Display>,
+        TOutExcluded: TryFrom<u64, Error: Display>,
+    {
+        let mut merged: Vec<Range<u64>> = Vec::new();
+        for (range, _) in self.iter::<Range<u64>>().filter(|(_, meta)| *meta == label) {
+            match merged.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => merged.push(range),
+            }
+        }
+        SortedRanges::try_from_ordered_iter_roi(merged, self.bounds)
+    }
+
+    /// Replaces each meta by the result of `f`
+    pub fn relabel<TNewMeta: PartialEq>(
+        self,
+        mut f: impl FnMut(TMeta) -> TNewMeta,
+    ) -> Result<SortedRangesMap<TIncluded, TExcluded, Vec<TNewMeta>>, BuildError> {
+        let bounds = self.bounds;
+        coalesce(
+            self.iter_owned::<Range<u64>>()
+                .map(|(range, meta)| (range, f(meta))),
+            bounds,
+        )
+    }
+
+    /// Ranges labeled `from` are labeled `into` afterwards
+    pub fn merge_labels(self, into: &TMeta, from: &TMeta) -> Result<Self, BuildError>
+    where
+        TMeta: PartialEq + Clone,
+    {
+        self.relabel(|meta| if meta == *from { into.clone() } else { meta })
+    }
+
+    /// Merges touching ranges with equal meta, so equal areas compare equal
+    pub fn normalize(self) -> Result<Self, BuildError>
+    where
+        TMeta: PartialEq,
+    {
+        let bounds = self.bounds;
+        coalesce(self.iter_owned::<Range<u64>>(), bounds)
+    }
+
+    /// Removes all ranges, for which `f` returns false
+    pub fn retain(self, mut f: impl FnMut(&TMeta) -> bool) -> Result<Self, BuildError>
+    where
+        TMeta: PartialEq,
+    {
+        let bounds = self.bounds;
+        coalesce(
+            self.iter_owned::<Range<u64>>().filter(|(_, meta)| f(meta)),
+            bounds,
+        )
+    }
+}
+
+fn coalesce<TIncluded, TExcluded, TMeta: PartialEq>(
+    ranges: impl Iterator<Item = (Range<u64>, TMeta)>,
+    bounds: crate::Rect<u32>,
+) -> Result<SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>, BuildError>
+where
+    TIncluded: TryFrom<u64>,
+    TExcluded: TryFrom<u64>,
+{
+    let mut merged: Vec<(Range<u64>, TMeta)> = Vec::with_capacity(ranges.size_hint().0);
+    for (range, meta) in ranges {
+        match merged.last_mut() {
+            Some((last, last_meta)) if last.end == range.start && *last_meta == meta => {
+                last.end = range.end
+            }
+            _ => merged.push((range, meta)),
+        }
+    }
+    SortedRangesMap::from_ordered_ranges(merged, bounds)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::{ImageDimension, Rect};
+
+    use super::*;
+
+    const SIZE: NonZero<u32> = NonZero::new(10).unwrap();
+
+    /// Touching ranges with different labels
+    fn map() -> SortedRangesMap<u8, u8, Vec<u8>> {
+        SortedRangesMap::from_ordered_ranges(
+            [(0..5, 1), (5..8, 2), (8..10, 1), (20..30, 3), (40..45, 2)],
+            Rect::new(0, 0, SIZE, SIZE),
+        )
+        .unwrap()
+    }
+
+    fn collect(map: &SortedRangesMap<u8, u8, Vec<u8>>) -> Vec<(Range<u64>, u8)> {
+        map.iter::<Range<u64>>()
+            .map(|(range, meta)| (range, *meta))
+            .collect()
+    }
+
+    #[test]
+    fn labels_and_extract() {
+        let map = map();
+        assert_eq!(
+            vec![&1, &2, &3],
+            map.labels().into_iter().collect::<Vec<_>>()
+        );
+        let twos = map.extract::<u8, u8>(&2).unwrap();
+        assert_eq!(map.bounds(), twos.bounds());
+        assert_eq!(
+            vec![5u64..8, 40..45],
+            twos.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+        );
+        assert!(map.extract::<u8, u8>(&4).unwrap().is_empty());
+    }
+
+    #[test]
+    fn merge_labels_coalesces() {
+        let merged = map().merge_labels(&1, &2).unwrap();
+        assert_eq!(vec![(0..10, 1), (20..30, 3), (40..45, 1)], collect(&merged));
+        let relabeled = map().relabel(|meta| meta % 2).unwrap();
+        assert_eq!(
+            vec![(0..5, 1), (5..8, 0), (8..10, 1), (20..30, 1), (40..45, 0)],
+            collect(&relabeled)
+        );
+    }
+
+    #[test]
+    fn retain_reports_gap_overflow() {
+        let retained = map().retain(|meta| *meta != 2).unwrap();
+        assert_eq!(vec![(0..5, 1), (8..10, 1), (20..30, 3)], collect(&retained));
+        let wide = SortedRangesMap::<u8, u8, Vec<u8>>::from_ordered_ranges(
+            [(0..1, 1), (200..201, 2), (400..401, 1)],
+            Rect::new(0, 0, NonZero::new(1000).unwrap(), SIZE),
+        )
+        .unwrap();
+        assert_eq!(
+            Err(BuildError::GapOverflow { gap: 399 }),
+            wide.retain(|meta| *meta == 1)
+        );
+    }
+}
@@ -7,7 +7,8 @@ use crate::{CreateRange, RangeToOffsetsIterMap, SortedRangesMap, UncheckedCast};
 impl<TIncluded: UncheckedCast<u64>, TExcluded: UncheckedCast<u64>, TMeta: Debug>
     SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>
 {
-    pub fn map_inplace<TIter, TFun>(self, f: TFun) -> Option<Self>
+    /// See `SortedRanges::map_inplace`
+    pub fn map_inplace<TIter, TFun>(self, f: TFun) -> Self
     where
         TIter: Iterator<Item = (RangeInclusive<u64>, TMeta)>,
         TFun: FnOnce(SourceIteratorMap<TIncluded, TExcluded, TMeta>) -> TIter,
@@ -58,7 +59,7 @@ impl<TIncluded: UncheckedCast<u64>, TExcluded: UncheckedCast<u64>, TMeta: Debug>
             }
         }
 
-        let not_empty = {
+        {
             let mut x = cell.borrow_mut();
             let col = &mut x.0;
             while let Some(tuple) = cache.pop_front() {
@@ -68,14 +69,11 @@ impl<TIncluded: UncheckedCast<u64>, TExcluded: UncheckedCast<u64>, TMeta: Debug>
             col.included.truncate(write_pos);
             col.excluded.truncate(write_pos);
             col.meta.truncate(write_pos);
-            !x.0.included.is_empty()
-        };
-        not_empty.then(move || {
-            Rc::try_unwrap(cell)
-                .expect("You are not allowed to move SourceIter outside the lambda")
-                .into_inner()
-                .0
-        })
+        }
+        Rc::try_unwrap(cell)
+            .expect("You are not allowed to move SourceIter outside the lambda")
+            .into_inner()
+            .0
     }
 }
 
@@ -0,0 +1,112 @@
+use std::{fmt::Display, num::NonZeroU32, ops::Range};
+
+use crate::{
+    BuildError, SortedRangesMap, UncheckedCast,
+    orientation::{Orientation, join_row_spans, split_into_row_spans},
+};
+
+/// Exact flips and rotations which carry the meta of each range along.
+/// See `SortedRanges::flip_horizontal` for the meaning of the parameters.
+/// Touching ranges are only merged, if their meta is equal.
+impl<TIncluded, TExcluded, TMeta> SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TMeta: Clone + PartialEq,
+{
+    pub fn flip_horizontal(&self, image_width: NonZeroU32) -> Result<Self, BuildError> {
+        self.reorient(Orientation::FlipHorizontal { image_width })
+    }
+
+    pub fn flip_vertical(&self, image_height: NonZeroU32) -> Result<Self, BuildError> {
+        self.reorient(Orientation::FlipVertical { image_height })
+    }
+
+    pub fn transpose(&self) -> Result<Self, BuildError> {
+        self.reorient(Orientation::Transpose)
+    }
+
+    pub fn rotate90(&self, image_height: NonZeroU32) -> Result<Self, BuildError> {
+        self.reorient(Orientation::Rotate90 { image_height })
+    }
+
+    pub fn rotate180(
+        &self,
+        image_width: NonZeroU32,
+        image_height: NonZeroU32,
+    ) -> Result<Self, BuildError> {
+        self.reorient(Orientation::Rotate180 {
+            image_width,
+            image_height,
+        })
+    }
+
+    pub fn rotate270(&self, image_width: NonZeroU32) -> Result<Self, BuildError> {
+        self.reorient(Orientation::Rotate270 { image_width })
+    }
+
+    fn reorient(&self, orientation: Orientation) -> Result<Self, BuildError> {
+        let bounds = orientation.apply_rect(self.bounds)?;
+        let spans = split_into_row_spans(
+            self.iter::<Range<u64>>().map(|(r, m)| (r, m.clone())),
+            self.bounds.width,
+        );
+        let spans = orientation.apply_spans(spans, self.bounds.width, self.bounds.height);
+        let ranges = join_row_spans(spans, bounds.width);
+        Self::from_ordered_ranges(ranges, bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::{ImageDimension, ImaskSet, Rect};
+
+    use super::*;
+
+    const SIZE: NonZeroU32 = NonZero::new(4).unwrap();
+
+    /// ```text
+    /// aa.b
+    /// ....
+    /// ```
+    fn sample() -> SortedRangesMap<u8, u8, Vec<char>> {
+        SortedRangesMap::try_from_ordered_iter(
+            [(0u32..2, 'a'), (3..4, 'b')].with_bounds(SIZE, NonZero::new(2).unwrap()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn flip_horizontal_keeps_meta() {
+        let flipped = sample().flip_horizontal(SIZE).unwrap();
+        assert_eq!(
+            vec![(0u64..1, &'b'), (2..4, &'a')],
+            flipped.iter::<Range<u64>>().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn transpose_splits_ranges_per_column() {
+        let transposed = sample().transpose().unwrap();
+        assert_eq!(
+            Rect::new(0, 0, NonZero::new(2).unwrap(), SIZE),
+            transposed.bounds()
+        );
+        assert_eq!(
+            vec![(0u64..1, &'a'), (2..3, &'a'), (6..7, &'b')],
+            transposed.iter::<Range<u64>>().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn rotate_back_and_forth() {
+        let original = sample();
+        let rotated = original
+            .rotate90(NonZero::new(2).unwrap())
+            .and_then(|x| x.rotate270(NonZero::new(2).unwrap()))
+            .unwrap();
+        assert_eq!(original, rotated);
+    }
+}
@@ -0,0 +1,335 @@
+use std::{cmp::Ordering, collections::BinaryHeap, iter::FusedIterator, num::NonZero, ops::Range};
+
+use crate::{BuildError, ImageDimension, OrderedRangeItem, Rect, SortedRangesMap};
+
+/// Flattens overlapping, labeled streams into disjoint ranges. Each pixel takes the meta of the
+/// covering range with the highest priority. On equal priority, the stream added first wins and
+/// within a stream, the later range wins.
+/// Each stream must be sorted by the start of its ranges, while ranges of a stream may overlap.
+/// Touching output ranges with equal meta are merged
+/// ```
+/// use std::num::NonZero;
+/// use imask::{NonZeroRange, OrderedRangeItem, PriorityPainter, Rect};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let item = |range, meta, priority| OrderedRangeItem { range: NonZeroRange::new(range), meta, priority };
+/// let far = vec![item(0..10, "wall", 1)];
+/// let close = vec![item(3..5, "person", 5), item(8..12, "person", 5)];
+/// let size = NonZero::new(20).unwrap();
+/// let painter = PriorityPainter::new([far.into_iter(), close.into_iter()], Rect::new(0, 0, size, size));
+/// assert_eq!(
+///     vec![(0..3, "wall"), (3..5, "person"), (5..8, "wall"), (8..12, "person")],
+///     painter.collect::<Vec<_>>()
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub struct PriorityPainter<I, TMeta> {
+    streams: Vec<I>,
+    pending: BinaryHeap<Pending<TMeta>>,
+    active: BinaryHeap<Active<TMeta>>,
+    position: u32,
+    unreleased: Option<(Range<u32>, TMeta)>,
+    bounds: Rect<u32>,
+    /// Number of activated ranges. Orders ranges of a stream, as they are activated in order
+    sequence: u64,
+}
+
+impl<I, TMeta> PriorityPainter<I, TMeta>
+where
+    I: Iterator<Item = OrderedRangeItem<TMeta>>,
+    TMeta: Clone + PartialEq,
+{
+    /// Ranges of all streams are relative to `bounds`
+    pub fn new(streams: impl IntoIterator<Item = I>, bounds: Rect<u32>) -> Self {
+        let mut painter = Self {
+            streams: streams.into_iter().collect(),
+            pending: BinaryHeap::new(),
+            active: BinaryHeap::new(),
+            position: 0,
+            unreleased: None,
+            bounds,
+            sequence: 0,
+        };
+        for stream in 0..painter.streams.len() {
+            painter.refill(stream, 0);
+        }
+        painter
+    }
+
+    /// Collects the painted ranges into a map with the bounds of the painter
+    pub fn try_into_map<TIncluded, TExcluded>(
+        self,
+    ) -> Result<SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>, BuildError>
+    where
+        TIncluded: TryFrom<u64>,
+        TExcluded: TryFrom<u64>,
+    {
+        let bounds = self.bounds;
+        SortedRangesMap::from_ordered_ranges(
+            self.map(|(range, meta)| (u64::from(range.start)..u64::from(range.end), meta)),
+            bounds,
+        )
+    }
+
+    fn refill(&mut self, stream: usize, previous_start: u32) {
+        if let Some(item) = self.streams[stream].next() {
+            debug_assert!(
+                previous_start <= item.range.start,
+                "Streams must be sorted by start"
+            );
+            self.pending.push(Pending { item, stream });
+        }
+    }
+
+    /// Next part covered by a single range. Neighbours may have the same meta
+    fn next_segment(&mut self) -> Option<(Range<u32>, TMeta)> {
+        loop {
+            self.drop_expired();
+            if self.active.is_empty() {
+                let next = self.pending.peek()?;
+                self.position = self.position.max(next.item.range.start);
+            }
+            while self
+                .pending
+                .peek()
+                .is_some_and(|p| p.item.range.start <= self.position)
+            {
+                let Pending { item, stream } = self.pending.pop().expect("Peeked before");
+                self.refill(stream, item.range.start);
+                if item.range.end > self.position {
+                    self.active.push(Active {
+                        end: item.range.end,
+                        priority: item.priority,
+                        stream,
+                        sequence: self.sequence,
+                        meta: item.meta,
+                    });
+                    self.sequence += 1;
+                }
+            }
+            self.drop_expired();
+            let Some(top) = self.active.peek() else {
+                continue;
+            };
+            let end = self
+                .pending
+                .peek()
+                .map_or(top.end, |p| top.end.min(p.item.range.start));
+            let segment = (self.position..end, top.meta.clone());
+            self.position = end;
+            return Some(segment);
+        }
+    }
+
+    /// Only the top is checked. Expired ranges below are dropped once they get to the top
+    fn drop_expired(&mut self) {
+        while self.active.peek().is_some_and(|a| a.end <= self.position) {
+            self.active.pop();
+        }
+    }
+}
+
+impl<I, TMeta> Iterator for PriorityPainter<I, TMeta>
+where
+    I: Iterator<Item = OrderedRangeItem<TMeta>>,
+    TMeta: Clone + PartialEq,
+{
+    type Item = (Range<u32>, TMeta);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Some((range, meta)) = self.next_segment() else {
+                return self.unreleased.take();
+            };
+            if let Some((last, last_meta)) = &mut self.unreleased
+                && last.end == range.start
+                && *last_meta == meta
+            {
+                last.end = range.end;
+                continue;
+            }
+            if let Some(released) = self.unreleased.replace((range, meta)) {
+                return Some(released);
+            }
+        }
+    }
+}
+
+impl<I, TMeta> FusedIterator for PriorityPainter<I, TMeta>
+where
+    I: Iterator<Item = OrderedRangeItem<TMeta>>,
+    TMeta: Clone + PartialEq,
+{
+}
+
+impl<I, TMeta> ImageDimension for PriorityPainter<I, TMeta> {
+    fn bounds(&self) -> Rect<u32> {
+        self.bounds
+    }
+
+    fn width(&self) -> NonZero<u32> {
+        self.bounds.width
+    }
+}
+
+/// Next item of a stream. The heap returns the smallest `OrderedRangeItem::comparator` first
+struct Pending<TMeta> {
+    item: OrderedRangeItem<TMeta>,
+    stream: usize,
+}
+
+impl<TMeta> Pending<TMeta> {
+    fn key(&self) -> ((u32, u32), usize) {
+        (self.item.comparator(), self.stream)
+    }
+}
+
+impl<TMeta> PartialEq for Pending<TMeta> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<TMeta> Eq for Pending<TMeta> {}
+
+impl<TMeta> PartialOrd for Pending<TMeta> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<TMeta> Ord for Pending<TMeta> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
+
+/// Range covering the current position. The heap returns the winning range first
+struct Active<TMeta> {
+    end: u32,
+    priority: u32,
+    stream: usize,
+    sequence: u64,
+    meta: TMeta,
+}
+
+impl<TMeta> Active<TMeta> {
+    /// Higher priority wins, then the earlier stream, then the later range of the stream
+    fn key(&self) -> (u32, std::cmp::Reverse<usize>, u64) {
+        (self.priority, std::cmp::Reverse(self.stream), self.sequence)
+    }
+}
+
+impl<TMeta> PartialEq for Active<TMeta> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<TMeta> Eq for Active<TMeta> {}
+
+impl<TMeta> PartialOrd for Active<TMeta> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<TMeta> Ord for Active<TMeta> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::NonZeroRange;
+
+    use super::*;
+
+    const SIZE: NonZero<u32> = NonZero::new(100).unwrap();
+
+    fn item(range: Range<u32>, meta: u8, priority: u32) -> OrderedRangeItem<u8> {
+        OrderedRangeItem {
+            range: NonZeroRange::new(range),
+            meta,
+            priority,
+        }
+    }
+
+    fn paint(streams: Vec<Vec<OrderedRangeItem<u8>>>) -> Vec<(Range<u32>, u8)> {
+        PriorityPainter::new(
+            streams.into_iter().map(Vec::into_iter),
+            Rect::new(0, 0, SIZE, SIZE),
+        )
+        .collect()
+    }
+
+    #[test]
+    fn higher_priority_wins() {
+        let painted = paint(vec![
+            vec![item(0..20, 1, 1), item(30..40, 1, 1)],
+            vec![item(5..35, 2, 2)],
+            vec![item(10..12, 3, 3), item(50..60, 3, 0)],
+        ]);
+        assert_eq!(
+            vec![
+                (0..5, 1),
+                (5..10, 2),
+                (10..12, 3),
+                (12..35, 2),
+                (35..40, 1),
+                (50..60, 3)
+            ],
+            painted
+        );
+    }
+
+    #[test]
+    fn nested_ranges_within_a_stream() {
+        // The long range reappears after the short one of higher priority ends
+        let painted = paint(vec![vec![
+            item(0..10, 1, 1),
+            item(2..4, 2, 2),
+            item(3..5, 1, 1),
+        ]]);
+        assert_eq!(vec![(0..2, 1), (2..4, 2), (4..10, 1)], painted);
+        // Equal priority: The first stream wins
+        let painted = paint(vec![vec![item(5..10, 1, 1)], vec![item(0..8, 2, 1)]]);
+        assert_eq!(vec![(0..5, 2), (5..10, 1)], painted);
+    }
+
+    #[test]
+    fn later_range_of_a_stream_wins_on_equal_priority() {
+        let painted = paint(vec![vec![
+            item(0..10, 1, 1),
+            item(2..4, 2, 1),
+            item(5..15, 3, 1),
+        ]]);
+        assert_eq!(vec![(0..2, 1), (2..4, 2), (4..5, 1), (5..15, 3)], painted);
+        // Stream order takes precedence over the order within a stream
+        let painted = paint(vec![
+            vec![item(0..10, 1, 1)],
+            vec![item(0..10, 2, 1), item(2..4, 3, 1)],
+        ]);
+        assert_eq!(vec![(0..10, 1)], painted);
+    }
+
+    #[test]
+    fn into_map_keeps_touching_labels() {
+        let painter = PriorityPainter::new(
+            [
+                vec![item(0..10, 1, 1)].into_iter(),
+                vec![item(10..20, 2, 1)].into_iter(),
+            ],
+            Rect::new(0, 0, SIZE, SIZE),
+        );
+        let map = painter.try_into_map::<u8, u8>().unwrap();
+        assert_eq!(
+            vec![(0..10, 1), (10..20, 2)],
+            map.iter::<Range<u64>>()
+                .map(|(range, meta)| (range, *meta))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+
+use crate::{BuildError, Rect, SortedRangesMap, UncheckedCast, build_error::check_bounds};
+
+#[derive(Serialize)]
+struct CompactRef<'a, TIncluded, TExcluded, TMeta> {
+    bounds: Rect<u32>,
+    excluded: &'a [TExcluded],
+    included: &'a [TIncluded],
+    meta: &'a [TMeta],
+}
+
+#[derive(Deserialize)]
+struct Compact<TIncluded, TExcluded, TMeta> {
+    bounds: Rect<u32>,
+    excluded: Vec<TExcluded>,
+    included: Vec<TIncluded>,
+    meta: Vec<TMeta>,
+}
+
+impl<TIncluded, TExcluded, TMeta> SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>
+where
+    TIncluded: TryFrom<u64>,
+    TExcluded: TryFrom<u64>,
+{
+    /// Touching ranges are accepted, as they separate different meta
+    fn try_from_ranges_in_bounds(
+        ranges: Vec<(Range<u64>, TMeta)>,
+        bounds: Rect<u32>,
+    ) -> Result<Self, BuildError> {
+        let mut previous_end = 0;
+        for (range, _) in &ranges {
+            if range.start < previous_end {
+                return Err(BuildError::Overlap {
+                    start: range.start,
+                    previous_end,
+                });
+            }
+            previous_end = range.end;
+        }
+        check_bounds(previous_end, bounds)?;
+        Self::from_ordered_ranges(ranges, bounds)
+    }
+}
+
+/// Compact representation: The ROI and the raw arrays of gaps, lengths and meta.
+/// Use `serde_map_ranges` for a human readable alternative
+impl<TIncluded, TExcluded, TMeta> Serialize for SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>
+where
+    TIncluded: Serialize,
+    TExcluded: Serialize,
+    TMeta: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CompactRef {
+            bounds: self.bounds,
+            excluded: &self.excluded,
+            included: &self.included,
+            meta: &self.meta,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, TIncluded, TExcluded, TMeta> Deserialize<'de>
+    for SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>
+where
+    TIncluded: Deserialize<'de> + UncheckedCast<u64> + TryFrom<u64>,
+    TExcluded: Deserialize<'de> + UncheckedCast<u64> + TryFrom<u64>,
+    TMeta: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let compact = Compact::<TIncluded, TExcluded, TMeta>::deserialize(deserializer)?;
+        if compact.included.len() != compact.excluded.len() {
+            return Err(D::Error::custom(BuildError::LengthMismatch {
+                included: compact.included.len(),
+                excluded: compact.excluded.len(),
+            }));
+        }
+        if compact.included.len() != compact.meta.len() {
+            return Err(D::Error::invalid_length(
+                compact.meta.len(),
+                &"one meta per included value",
+            ));
+        }
+        let mut pos = 0u64;
+        let ranges = compact
+            .excluded
+            .iter()
+            .zip(&compact.included)
+            .zip(compact.meta)
+            .map(|((&e, &i), meta)| {
+                let start = pos.saturating_add(e.cast_unchecked());
+                pos = start.saturating_add(i.cast_unchecked());
+                (start..pos, meta)
+            })
+            .collect();
+        Self::try_from_ranges_in_bounds(ranges, compact.bounds).map_err(D::Error::custom)
+    }
+}
+
+/// Human readable representation of `SortedRangesMap` as ROI and `[[start, end, meta], ...]`
+/// relative to the ROI. Use it with `#[serde(with = "imask::serde_map_ranges")]`
+pub mod serde_map_ranges {
+    use super::*;
+
+    struct RangesRef<'a, TIncluded, TExcluded, TMeta>(
+        &'a SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>,
+    );
+
+    impl<TIncluded, TExcluded, TMeta> Serialize for RangesRef<'_, TIncluded, TExcluded, TMeta>
+    where
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+        TMeta: Serialize,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(
+                self.0
+                    .iter::<Range<u64>>()
+                    .map(|(r, meta)| (r.start, r.end, meta)),
+            )
+        }
+    }
+
+    #[derive(Serialize)]
+    #[serde(bound = "RangesRef<'a, TIncluded, TExcluded, TMeta>: Serialize")]
+    struct ReadableRef<'a, TIncluded, TExcluded, TMeta> {
+        bounds: Rect<u32>,
+        ranges: RangesRef<'a, TIncluded, TExcluded, TMeta>,
+    }
+
+    #[derive(Deserialize)]
+    struct Readable<TMeta> {
+        bounds: Rect<u32>,
+        ranges: Vec<(u64, u64, TMeta)>,
+    }
+
+    pub fn serialize<S, TIncluded, TExcluded, TMeta>(
+        map: &SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+        TMeta: Serialize,
+    {
+        ReadableRef {
+            bounds: map.bounds,
+            ranges: RangesRef(map),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, TIncluded, TExcluded, TMeta>(
+        deserializer: D,
+    ) -> Result<SortedRangesMap<TIncluded, TExcluded, Vec<TMeta>>, D::Error>
+    where
+        D: Deserializer<'de>,
+        TIncluded: TryFrom<u64>,
+        TExcluded: TryFrom<u64>,
+        TMeta: Deserialize<'de>,
+    {
+        let readable = Readable::<TMeta>::deserialize(deserializer)?;
+        let ranges = readable
+            .ranges
+            .into_iter()
+            .map(|(start, end, meta)| (start..end, meta))
+            .collect();
+        SortedRangesMap::try_from_ranges_in_bounds(ranges, readable.bounds)
+            .map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::ImaskSet;
+
+    use super::*;
+
+    fn sample() -> SortedRangesMap<u8, u8, Vec<char>> {
+        let size = NonZero::new(10).unwrap();
+        SortedRangesMap::try_from_ordered_iter(
+            [(3u32..5, 'a'), (8..16, 'b')].with_bounds(size, size),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn compact_roundtrip() {
+        let map = sample();
+        let json = serde_json::to_string(&map).unwrap();
+        assert_eq!(
+            r#"{"bounds":{"x":0,"y":0,"width":10,"height":10},"excluded":[3,3],"included":[2,8],"meta":["a","b"]}"#,
+            json
+        );
+        assert_eq!(map, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn readable_roundtrip_accepts_touching_ranges() {
+        let json =
+            r#"{"bounds":{"x":0,"y":0,"width":10,"height":10},"ranges":[[3,5,"a"],[5,16,"b"]]}"#;
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let map: SortedRangesMap<u8, u8, Vec<char>> =
+            serde_map_ranges::deserialize(&mut deserializer).unwrap();
+        let mut serialized = Vec::new();
+        serde_map_ranges::serialize(&map, &mut serde_json::Serializer::new(&mut serialized))
+            .unwrap();
+        assert_eq!(json.as_bytes(), serialized);
+
+        let overlapping = json.replace("[5,16", "[4,16");
+        let mut deserializer = serde_json::Deserializer::from_str(&overlapping);
+        let error =
+            serde_map_ranges::deserialize::<_, u8, u8, char>(&mut deserializer).unwrap_err();
+        assert!(error.to_string().contains("overlaps"), "{error}");
+    }
+
+    #[test]
+    fn reject_missing_meta() {
+        let json = r#"{"bounds":{"x":0,"y":0,"width":10,"height":10},"excluded":[3],"included":[2],"meta":[]}"#;
+        assert!(serde_json::from_str::<SortedRangesMap<u8, u8, Vec<char>>>(json).is_err());
+    }
+}
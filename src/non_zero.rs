@@ -76,6 +76,28 @@ impl<T: PartialOrd + Add<Output = T> + One> TryFrom<std::ops::RangeInclusive<T>>
     }
 }
 
+/// Serialized as `[start, end]`. Empty ranges are rejected
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> serde::Serialize for NonZeroRange<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.0.start, &self.0.end).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de> + PartialOrd + Debug> serde::Deserialize<'de>
+    for NonZeroRange<T>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (start, end) = <(T, T)>::deserialize(deserializer)?;
+        (start..end)
+            .try_into()
+            .map_err(|e: RangeZeroLenghtError<_>| {
+                serde::de::Error::custom(format_args!("Range {:?} is empty", e.0))
+            })
+    }
+}
+
 pub trait SignedNonZeroable: Sized {
     type NonZero: Into<Self> + Copy;
     fn add_nonzero(self, other: Self::NonZero) -> Self;
@@ -413,4 +435,23 @@ mod tests {
             }
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let range = NonZeroRange::new(5u32..10);
+        let json = serde_json::to_string(&range).unwrap();
+        assert_eq!("[5,10]", json);
+        assert_eq!(
+            range,
+            serde_json::from_str::<NonZeroRange<u32>>(&json).unwrap()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_rejects_empty_range() {
+        let error = serde_json::from_str::<NonZeroRange<u32>>("[5, 5]").unwrap_err();
+        assert!(error.to_string().contains("is empty"), "{error}");
+    }
 }
@@ -0,0 +1,378 @@
+use std::{num::NonZeroU32, ops::Range};
+
+use crate::{BuildError, Rect};
+
+/// Exact, lossless reorientations of a mask. Rotations are clockwise.
+/// Each variant carries the parent image dimensions needed to reposition the ROI
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Orientation {
+    FlipHorizontal {
+        image_width: NonZeroU32,
+    },
+    FlipVertical {
+        image_height: NonZeroU32,
+    },
+    Transpose,
+    Rotate90 {
+        image_height: NonZeroU32,
+    },
+    Rotate180 {
+        image_width: NonZeroU32,
+        image_height: NonZeroU32,
+    },
+    Rotate270 {
+        image_width: NonZeroU32,
+    },
+}
+
+/// Part of a range within a single row. `start..end` are columns, `end` is exclusive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RowSpan<M> {
+    pub y: u32,
+    pub start: u32,
+    pub end: u32,
+    pub meta: M,
+}
+
+/// Vertical run of `rows` covering all columns `columns` with the same meta
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ColumnBlock<M> {
+    pub columns: Range<u32>,
+    pub rows: Range<u32>,
+    pub meta: M,
+}
+
+impl Orientation {
+    /// Position of the transformed ROI within the transformed parent image
+    pub fn apply_rect(self, roi: Rect<u32>) -> Result<Rect<u32>, BuildError> {
+        let flipped_x = |image_width: NonZeroU32| {
+            image_width
+                .get()
+                .checked_sub(roi.x)
+                .and_then(|x| x.checked_sub(roi.width.get()))
+                .ok_or(BuildError::RoiExceedsImageWidth { roi, image_width })
+        };
+        let flipped_y = |image_height: NonZeroU32| {
+            image_height
+                .get()
+                .checked_sub(roi.y)
+                .and_then(|y| y.checked_sub(roi.height.get()))
+                .ok_or(BuildError::RoiExceedsImageHeight { roi, image_height })
+        };
+        Ok(match self {
+            Orientation::FlipHorizontal { image_width } => Rect {
+                x: flipped_x(image_width)?,
+                ..roi
+            },
+            Orientation::FlipVertical { image_height } => Rect {
+                y: flipped_y(image_height)?,
+                ..roi
+            },
+            Orientation::Transpose => Rect::new(roi.y, roi.x, roi.height, roi.width),
+            Orientation::Rotate90 { image_height } => {
+                Rect::new(flipped_y(image_height)?, roi.x, roi.height, roi.width)
+            }
+            Orientation::Rotate180 {
+                image_width,
+                image_height,
+            } => Rect::new(
+                flipped_x(image_width)?,
+                flipped_y(image_height)?,
+                roi.width,
+                roi.height,
+            ),
+            Orientation::Rotate270 { image_width } => {
+                Rect::new(roi.y, flipped_x(image_width)?, roi.height, roi.width)
+            }
+        })
+    }
+
+    /// Transforms spans of a `width` x `height` area. The result is sorted by `(y, start)`
+    pub fn apply_spans<M: Clone + PartialEq>(
+        self,
+        spans: Vec<RowSpan<M>>,
+        width: NonZeroU32,
+        height: NonZeroU32,
+    ) -> Vec<RowSpan<M>> {
+        let (w, h) = (width.get(), height.get());
+        let mut spans = match self {
+            Orientation::FlipHorizontal { .. } => flip_x(spans, w),
+            Orientation::FlipVertical { .. } => flip_y(spans, h),
+            Orientation::Transpose => transpose(spans),
+            Orientation::Rotate90 { .. } => flip_x(transpose(spans), h),
+            Orientation::Rotate180 { .. } => flip_y(flip_x(spans, w), h),
+            Orientation::Rotate270 { .. } => flip_y(transpose(spans), w),
+        };
+        spans.sort_unstable_by_key(|s| (s.y, s.start));
+        spans
+    }
+}
+
+fn flip_x<M>(mut spans: Vec<RowSpan<M>>, width: u32) -> Vec<RowSpan<M>> {
+    for s in spans.iter_mut() {
+        (s.start, s.end) = (width - s.end, width - s.start);
+    }
+    spans
+}
+
+fn flip_y<M>(mut spans: Vec<RowSpan<M>>, height: u32) -> Vec<RowSpan<M>> {
+    for s in spans.iter_mut() {
+        s.y = height - 1 - s.y;
+    }
+    spans
+}
+
+fn transpose<M: Clone + PartialEq>(spans: Vec<RowSpan<M>>) -> Vec<RowSpan<M>> {
+    column_blocks(spans)
+        .into_iter()
+        .flat_map(|block| {
+            let rows = block.rows;
+            block.columns.map(move |y| RowSpan {
+                y,
+                start: rows.start,
+                end: rows.end,
+                meta: block.meta.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Sweeps over rows sorted by `(y, start)` and keeps track of active vertical runs.
+/// A run ends as soon as a row doesn't cover its columns with the same meta.
+/// Blocks are returned in the order they are closed.
+pub(crate) fn column_blocks<M: Clone + PartialEq>(
+    spans: impl IntoIterator<Item = RowSpan<M>>,
+) -> Vec<ColumnBlock<M>> {
+    let mut done = Vec::new();
+    let mut active: Vec<ColumnBlock<M>> = Vec::new();
+    let mut row: Vec<RowSpan<M>> = Vec::new();
+    let mut spans = spans.into_iter().peekable();
+
+    while let Some(first) = spans.next() {
+        let y = first.y;
+        row.clear();
+        row.push(first);
+        while let Some(s) = spans.next_if(|s| s.y == y) {
+            row.push(s);
+        }
+        if active.first().is_some_and(|b| b.rows.end < y) {
+            done.append(&mut active);
+        }
+        active = advance_blocks(std::mem::take(&mut active), &row, y, &mut done);
+    }
+    done.append(&mut active);
+    done
+}
+
+fn advance_blocks<M: Clone + PartialEq>(
+    active: Vec<ColumnBlock<M>>,
+    row: &[RowSpan<M>],
+    y: u32,
+    done: &mut Vec<ColumnBlock<M>>,
+) -> Vec<ColumnBlock<M>> {
+    let mut next: Vec<ColumnBlock<M>> = Vec::with_capacity(active.len().max(row.len()));
+    let push = |next: &mut Vec<ColumnBlock<M>>, block: ColumnBlock<M>| {
+        if let Some(last) = next.last_mut()
+            && last.columns.end == block.columns.start
+            && last.rows == block.rows
+            && last.meta == block.meta
+        {
+            last.columns.end = block.columns.end;
+        } else {
+            next.push(block);
+        }
+    };
+    let close = |block: ColumnBlock<M>, columns: Range<u32>| ColumnBlock {
+        columns,
+        rows: block.rows.start..y,
+        meta: block.meta,
+    };
+    let open = |span: &RowSpan<M>, columns: Range<u32>| ColumnBlock {
+        columns,
+        rows: y..y + 1,
+        meta: span.meta.clone(),
+    };
+
+    let mut blocks = active.into_iter();
+    let mut spans = row.iter();
+    let mut block = blocks.next();
+    let mut span = spans.next().map(|s| (s, s.start));
+
+    loop {
+        match (block.take(), span) {
+            (None, None) => break,
+            (Some(b), None) => {
+                let columns = b.columns.clone();
+                done.push(close(b, columns));
+                block = blocks.next();
+            }
+            (None, Some((s, start))) => {
+                push(&mut next, open(s, start..s.end));
+                span = spans.next().map(|s| (s, s.start));
+            }
+            (Some(b), Some((s, start))) => {
+                if b.columns.end <= start {
+                    let columns = b.columns.clone();
+                    done.push(close(b, columns));
+                    block = blocks.next();
+                } else if s.end <= b.columns.start {
+                    push(&mut next, open(s, start..s.end));
+                    span = spans.next().map(|s| (s, s.start));
+                    block = Some(b);
+                } else if b.columns.start < start {
+                    done.push(close(b.clone(), b.columns.start..start));
+                    block = Some(ColumnBlock {
+                        columns: start..b.columns.end,
+                        ..b
+                    });
+                } else if start < b.columns.start {
+                    push(&mut next, open(s, start..b.columns.start));
+                    span = Some((s, b.columns.start));
+                    block = Some(b);
+                } else {
+                    let end = b.columns.end.min(s.end);
+                    if b.meta == s.meta {
+                        push(
+                            &mut next,
+                            ColumnBlock {
+                                columns: start..end,
+                                rows: b.rows.start..y + 1,
+                                meta: b.meta.clone(),
+                            },
+                        );
+                    } else {
+                        done.push(close(b.clone(), start..end));
+                        push(&mut next, open(s, start..end));
+                    }
+                    block = (end < b.columns.end).then(|| ColumnBlock {
+                        columns: end..b.columns.end,
+                        ..b
+                    });
+                    if block.is_none() {
+                        block = blocks.next();
+                    }
+                    span = if end < s.end {
+                        Some((s, end))
+                    } else {
+                        spans.next().map(|s| (s, s.start))
+                    };
+                }
+            }
+        }
+    }
+    next
+}
+
+/// Cuts ranges in a area of `width` into spans per row
+pub(crate) fn split_into_row_spans<M: Clone>(
+    ranges: impl IntoIterator<Item = (Range<u64>, M)>,
+    width: NonZeroU32,
+) -> Vec<RowSpan<M>> {
+    let width = u64::from(width.get());
+    let mut spans = Vec::new();
+    for (range, meta) in ranges {
+        let mut pos = range.start;
+        while pos < range.end {
+            let y = pos / width;
+            let row_end = range.end.min((y + 1) * width);
+            spans.push(RowSpan {
+                y: y as u32,
+                start: (pos - y * width) as u32,
+                end: (row_end - y * width) as u32,
+                meta: meta.clone(),
+            });
+            pos = row_end;
+        }
+    }
+    spans
+}
+
+/// Inverse of `split_into_row_spans`. Touching spans with equal meta are merged
+pub(crate) fn join_row_spans<M: PartialEq>(
+    spans: impl IntoIterator<Item = RowSpan<M>>,
+    width: NonZeroU32,
+) -> Vec<(Range<u64>, M)> {
+    let width = u64::from(width.get());
+    let mut ranges: Vec<(Range<u64>, M)> = Vec::new();
+    for s in spans {
+        let offset = u64::from(s.y) * width;
+        let range = offset + u64::from(s.start)..offset + u64::from(s.end);
+        match ranges.last_mut() {
+            Some((last, meta)) if last.end == range.start && *meta == s.meta => {
+                last.end = range.end
+            }
+            _ => ranges.push((range, s.meta)),
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(y: u32, x: Range<u32>) -> RowSpan<()> {
+        RowSpan {
+            y,
+            start: x.start,
+            end: x.end,
+            meta: (),
+        }
+    }
+
+    #[test]
+    fn column_blocks_split_on_partial_overlap() {
+        let blocks = column_blocks([span(0, 0..4), span(1, 2..6), span(3, 2..3)]);
+        let mut blocks: Vec<_> = blocks.into_iter().map(|b| (b.columns, b.rows)).collect();
+        blocks.sort_by_key(|(c, r)| (c.start, r.start));
+        assert_eq!(
+            blocks,
+            vec![(0..2, 0..1), (2..4, 0..2), (2..3, 3..4), (4..6, 1..2)]
+        );
+    }
+
+    #[test]
+    fn column_blocks_separate_different_meta() {
+        let blocks = column_blocks([
+            RowSpan {
+                y: 0,
+                start: 0,
+                end: 2,
+                meta: 'a',
+            },
+            RowSpan {
+                y: 1,
+                start: 0,
+                end: 2,
+                meta: 'b',
+            },
+        ]);
+        assert_eq!(
+            blocks,
+            vec![
+                ColumnBlock {
+                    columns: 0..2,
+                    rows: 0..1,
+                    meta: 'a'
+                },
+                ColumnBlock {
+                    columns: 0..2,
+                    rows: 1..2,
+                    meta: 'b'
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn split_and_join_roundtrip() {
+        let width = NonZeroU32::new(10).unwrap();
+        let ranges = vec![(3u64..25, ()), (27..28, ())];
+        let spans = split_into_row_spans(ranges.clone(), width);
+        assert_eq!(
+            spans,
+            vec![span(0, 3..10), span(1, 0..10), span(2, 0..5), span(2, 7..8)]
+        );
+        assert_eq!(join_row_spans(spans, width), ranges);
+    }
+}
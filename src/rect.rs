@@ -6,7 +6,11 @@ use std::{
 use crate::{CreateRange, NonZeroRange, RectIterator, SignedNonZeroable, UncheckedCast};
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-#[cfg_attr(feature = "rkyv", derive(rkyv::Archive))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect<T: SignedNonZeroable> {
     pub x: T,
     pub y: T,
@@ -14,6 +18,18 @@ pub struct Rect<T: SignedNonZeroable> {
     pub height: T::NonZero,
 }
 
+#[cfg(feature = "rkyv")]
+impl ArchivedRect<u32> {
+    pub fn to_native(&self) -> Rect<u32> {
+        Rect {
+            x: self.x.to_native(),
+            y: self.y.to_native(),
+            width: self.width.into(),
+            height: self.height.into(),
+        }
+    }
+}
+
 impl<T: SignedNonZeroable + Debug> Debug for Rect<T>
 where
     T::NonZero: Debug,
@@ -1,60 +1,118 @@
 use std::{
     cmp::Ord,
     fmt::{Debug, Display},
-    io,
     num::{NonZero, NonZeroU32, NonZeroU64},
     ops::{Add, Div, Mul, Rem, Sub},
 };
 
 use crate::{
-    CreateRange, ImageDimension, NonZeroRange, Rect, SignedNonZeroable, Span, SpanIntoRangesIter,
-    UncheckedCast, WithBounds, WithRoi, span,
+    BuildError, CreateRange, ImageDimension, NonZeroRange, Rect, SignedNonZeroable, Span,
+    SpanIntoRangesIter, UncheckedCast, WithBounds, WithRoi,
+    build_error::{check_bounds, checked_gap, checked_len, checked_offset},
+    span,
 };
 #[cfg(feature = "range-set-blaze-0_5")]
 use num_traits::{CheckedSub, One, SaturatingSub, Zero};
 
-fn invalid_data<T: Display>(e: T) -> std::io::Error {
-    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+fn invalid_position<T: Display>(e: T) -> BuildError {
+    BuildError::InvalidPosition(e.to_string())
 }
 
-mod bounds_inspector;
-// mod chunk_by_row;
 mod affine_transform;
+mod any_sorted_ranges;
+#[cfg(feature = "rkyv")]
+mod archived;
+mod bounds_inspector;
+mod chunk_by_row;
+mod chunked;
 mod clip_2d;
+mod column_major;
 #[cfg(feature = "range-set-blaze-0_5")]
 mod dilate;
+mod edit;
 #[cfg(feature = "async-io")]
 mod future;
+mod homography;
 mod iter;
 mod iter_global;
 mod map_inplace;
 mod offsets_iter;
+mod orientation;
+#[cfg(feature = "rayon")]
+mod par_bands;
+mod place;
+mod pyramid;
 mod rect;
+mod resize;
 mod sanitize_sorted_disjoint;
-// mod split_rows;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod sorted_ranges_ref;
+mod split_rows;
+mod tiled;
 
 pub use affine_transform::*;
+pub use any_sorted_ranges::*;
+#[cfg(feature = "rkyv")]
+pub use archived::*;
 pub use bounds_inspector::*;
-// pub use chunk_by_row::*;
+pub use chunk_by_row::*;
+pub use chunked::*;
 pub use clip_2d::*;
+pub use column_major::*;
 #[cfg(feature = "range-set-blaze-0_5")]
 pub use dilate::*;
+pub use edit::SortedRangesEditor;
+pub use homography::*;
 pub use iter::*;
 pub use iter_global::*;
 pub use map_inplace::*;
 pub use offsets_iter::*;
+#[cfg(feature = "rayon")]
+pub use par_bands::*;
+pub use place::*;
+pub use pyramid::*;
 pub use rect::*;
 pub use sanitize_sorted_disjoint::*;
-// pub use split_rows::*;
+#[cfg(feature = "serde")]
+pub use serde_impl::serde_ranges;
+pub use sorted_ranges_ref::*;
+pub use split_rows::*;
+pub use tiled::*;
 
 pub trait ImaskSet: IntoIterator + Sized {
-    // /// # Panics
-    // /// If the previous RowIterator is kept when getting the next RowIterator
-    // fn chunk_by_row_lending<R: CreateRange<Item: SignedNonZeroable>>(
-    //     self,
-    // ) -> ChunkByRowRanges<Self::IntoIter, R> {
-    //     ChunkByRowRanges::new(self.into_iter())
-    // }
+    /// Groups the ranges by row as `(row, ranges)`. Rows without ranges are skipped.
+    /// Ranges crossing a row end are split. Rows and ranges stay relative to the ROI
+    /// # Panics
+    /// In debug builds, if the previous row iterator is kept when getting the next row
+    fn chunk_by_row_lending(self) -> ChunkByRowRanges<Self::IntoIter, Self::Item>
+    where
+        Self::Item: CreateRange,
+    {
+        ChunkByRowRanges::new(self.into_iter())
+    }
+
+    /// Like `chunk_by_row_lending`, but yields the ranges of each row as x-ranges
+    /// ```
+    /// use std::{num::NonZero, ops::Range};
+    /// use imask::{ImaskSet, Rect};
+    ///
+    /// let roi = Rect::new(5, 5, NonZero::new(10).unwrap(), NonZero::new(10).unwrap());
+    /// let rows = [2u32..4, 8..13, 35..36]
+    ///     .with_roi(roi)
+    ///     .rows()
+    ///     .map(|(y, xs)| (y, xs.collect::<Vec<_>>()))
+    ///     .collect::<Vec<_>>();
+    /// assert_eq!(vec![(0, vec![2..4, 8..10]), (1, vec![0..3]), (3, vec![5..6])], rows);
+    /// ```
+    /// # Panics
+    /// In debug builds, if the previous row iterator is kept when getting the next row
+    fn rows(self) -> RowsIter<Self::IntoIter, Self::Item>
+    where
+        Self::Item: CreateRange,
+    {
+        RowsIter::new(self.into_iter())
+    }
 
     fn inspect_bounds<R: CreateRange>(self) -> BoundsInspector<Self::IntoIter, R> {
         BoundsInspector::new(self.into_iter())
@@ -66,6 +124,9 @@ pub trait ImaskSet: IntoIterator + Sized {
         crate::span::Union::new(self.into_iter(), other.into_iter())
     }
 
+    /// Clips the ranges to `roi` and returns them relative to it. A range starting right of the
+    /// ROI continues at the next row and a range ending left of it stops at the previous row, so
+    /// only rows without any pixel inside the ROI are dropped
     fn try_clip_2d(
         self,
         roi: Rect<u32>,
@@ -76,12 +137,24 @@ pub trait ImaskSet: IntoIterator + Sized {
         Clip2dIter::try_new(self.into_iter(), roi)
     }
 
-    // fn split_rows(self) -> SplitRowsIter<Self::IntoIter, Self::Item>
-    // where
-    //     Self::IntoIter: ImageDimension,
-    // {
-    //     SplitRowsIter::new(self.into_iter())
-    // }
+    /// Clips `(range, meta)` items, e.g. of `SortedRangesMap::iter`, keeping the meta of each range
+    fn try_clip_2d_meta(
+        self,
+        roi: Rect<u32>,
+    ) -> Result<Clip2dMetaIter<Self::IntoIter>, RoiWidthExceedsOriginal>
+    where
+        Self::IntoIter: ImageDimension,
+    {
+        Clip2dMetaIter::try_new(self.into_iter(), roi)
+    }
+
+    /// Splits ranges at the end of each row of the ROI
+    fn split_rows(self) -> SplitRowsIter<Self::IntoIter, Self::Item>
+    where
+        Self::IntoIter: ImageDimension,
+    {
+        SplitRowsIter::new(self.into_iter())
+    }
 
     fn into_ranges<TOut: CreateRange<Item: SignedNonZeroable>>(
         self,
@@ -100,6 +173,41 @@ pub trait ImaskSet: IntoIterator + Sized {
         SanitizeSortedDisjoint::new(self.into_iter())
     }
 
+    /// Applies `matrix`, which maps pixel coordinates of the parent image of the input to the parent
+    /// image of the output. The result contains all pixels of `output_bounds`, whose center is covered
+    /// by a transformed input pixel, as merged and sorted ranges relative to `output_bounds`
+    fn affine_transform(
+        self,
+        matrix: &nalgebra::Matrix3<f64>,
+        output_bounds: Rect<u32>,
+    ) -> AffineTransformIter
+    where
+        Self::Item: CreateRange<Item: Into<u64>>,
+        Self::IntoIter: ImageDimension,
+    {
+        AffineTransformIter::new(self.into_iter(), matrix, output_bounds)
+    }
+
+    /// Like `affine_transform`, but `matrix` is a homography. Coordinates are divided by the projective
+    /// component. Geometry behind the horizon (w <= 0) is clipped away
+    fn homography(self, matrix: &nalgebra::Matrix3<f64>, output_bounds: Rect<u32>) -> HomographyIter
+    where
+        Self::Item: CreateRange<Item: Into<u64>>,
+        Self::IntoIter: ImageDimension,
+    {
+        HomographyIter::new(self.into_iter(), matrix, output_bounds)
+    }
+
+    /// Moves the mask by `dx`/`dy` pixels within the parent image and clips it to `target`.
+    /// Unlike `try_clip_2d`, `target` may exceed the source on every side, e.g. to paste the mask of
+    /// a crop back into the full image. See `SortedRanges::place_subpixel` for fractional offsets
+    fn place(self, target: Rect<u32>, dx: i64, dy: i64) -> PlaceIter<Self::IntoIter>
+    where
+        Self::IntoIter: ImageDimension,
+    {
+        PlaceIter::new(self.into_iter(), target, dx, dy)
+    }
+
     fn with_roi(self, roi: Rect<u32>) -> WithRoi<Self::IntoIter> {
         WithRoi::new(self.into_iter(), roi)
     }
@@ -140,9 +248,12 @@ impl<I: IntoIterator> ImaskSet for I {}
 ///
 /// Included.len() = excluded.len() + 1
 ///
-/// Meta is expected to be indexable for each included range
+/// Masks without any range are valid and keep their bounds
 #[derive(Clone, Eq, PartialEq)]
-#[cfg_attr(feature = "rkyv", derive(rkyv::Archive))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
 pub struct SortedRanges<TIncluded, TExcluded> {
     included: Vec<TIncluded>,
     excluded: Vec<TExcluded>,
@@ -150,7 +261,7 @@ pub struct SortedRanges<TIncluded, TExcluded> {
 }
 impl<TIncluded, TExcluded> Debug for SortedRanges<TIncluded, TExcluded> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("NonEmptyOrderedRanges")
+        f.debug_struct("SortedRanges")
             .field("range_count", &self.included.len())
             .field("bounds", &self.bounds)
             .finish()
@@ -167,37 +278,29 @@ where
     TIncluded: TryFrom<u64, Error: Display>,
     TExcluded: TryFrom<u64, Error: Display>,
 {
-    fn new<TRange>(first_range: TRange, size_hint: usize) -> Result<Self, io::Error>
-    where
-        TRange: CreateRange<Item: TryInto<u64, Error: Display>>,
-    {
-        let (start_u64, end_u64) = (
-            first_range.start().try_into().map_err(invalid_data)?,
-            first_range.end().try_into().map_err(invalid_data)?,
-        );
-        let first_len = create_checked(start_u64, end_u64)?;
-        let initial_offset = TExcluded::try_from(start_u64).map_err(invalid_data)?;
-        let mut included = Vec::<TIncluded>::with_capacity(size_hint);
-        let mut excluded = Vec::<TExcluded>::with_capacity(size_hint);
-        included.push(first_len);
-        excluded.push(initial_offset);
-        Ok(Self {
-            included,
-            excluded,
-            cur_pos: end_u64,
-        })
+    fn with_capacity(size_hint: usize) -> Self {
+        Self {
+            included: Vec::with_capacity(size_hint),
+            excluded: Vec::with_capacity(size_hint),
+            cur_pos: 0,
+        }
     }
 
-    fn add<TRange>(&mut self, range: TRange) -> Result<(), io::Error>
+    fn add<TRange>(&mut self, range: TRange) -> Result<(), BuildError>
     where
         TRange: CreateRange<Item: TryInto<u64, Error: Display>>,
     {
         let (start_u64, end_u64) = (
-            range.start().try_into().map_err(invalid_data)?,
-            range.end().try_into().map_err(invalid_data)?,
+            range.start().try_into().map_err(invalid_position)?,
+            range.end().try_into().map_err(invalid_position)?,
         );
-        self.excluded.push(create_checked(self.cur_pos, start_u64)?);
-        self.included.push(create_checked(start_u64, end_u64)?);
+        let len = checked_len(start_u64, end_u64)?;
+        if self.included.is_empty() {
+            self.excluded.push(checked_offset(start_u64)?);
+        } else {
+            self.excluded.push(checked_gap(self.cur_pos, start_u64)?);
+        }
+        self.included.push(len);
 
         // let gap = start_u64.checked_sub(self.cur_pos).ok_or_else(|| {
         //     io::Error::new(
@@ -227,19 +330,63 @@ where
         self.cur_pos = end_u64;
         Ok(())
     }
-    fn build(self, bounds: Rect<u32>) -> SortedRanges<TIncluded, TExcluded> {
-        SortedRanges {
+    /// Like `add`, but splits values, which don't fit into their type, into continuation entries
+    fn add_split<TRange>(&mut self, range: TRange) -> Result<(), BuildError>
+    where
+        TRange: CreateRange<Item: TryInto<u64, Error: Display>>,
+        TIncluded: num_traits::Bounded + UncheckedCast<u64>,
+        TExcluded: num_traits::Bounded + UncheckedCast<u64>,
+        u64: UncheckedCast<TIncluded> + UncheckedCast<TExcluded>,
+    {
+        let (start_u64, end_u64) = (
+            range.start().try_into().map_err(invalid_position)?,
+            range.end().try_into().map_err(invalid_position)?,
+        );
+        let mut len = checked_len::<u64>(start_u64, end_u64)?;
+        let mut gap = if self.included.is_empty() {
+            start_u64
+        } else {
+            checked_gap::<u64>(self.cur_pos, start_u64)?
+        };
+
+        let max_gap: u64 = TExcluded::max_value().cast_unchecked();
+        while gap > max_gap {
+            self.excluded.push(TExcluded::max_value());
+            self.included.push(0u64.cast_unchecked());
+            gap -= max_gap;
+        }
+        self.excluded.push(gap.cast_unchecked());
+
+        let max_len: u64 = TIncluded::max_value().cast_unchecked();
+        while len > max_len {
+            self.included.push(TIncluded::max_value());
+            self.excluded.push(0u64.cast_unchecked());
+            len -= max_len;
+        }
+        self.included.push(len.cast_unchecked());
+
+        self.cur_pos = end_u64;
+        Ok(())
+    }
+    /// Fails, if the ranges exceed the pixels of `bounds`
+    fn build(self, bounds: Rect<u32>) -> Result<SortedRanges<TIncluded, TExcluded>, BuildError> {
+        check_bounds(self.cur_pos, bounds)?;
+        Ok(SortedRanges {
             included: self.included,
             excluded: self.excluded,
             bounds,
-        }
+        })
     }
 
-    fn build_global(self, width: NonZeroU32) -> io::Result<SortedRanges<TIncluded, TExcluded>> {
-        let height = u32::try_from(self.cur_pos / NonZeroU64::from(width) + 1)
+    fn build_global(
+        self,
+        width: NonZeroU32,
+    ) -> Result<SortedRanges<TIncluded, TExcluded>, BuildError> {
+        let height = self.cur_pos / NonZeroU64::from(width) + 1;
+        let height = u32::try_from(height)
             .ok()
             .and_then(NonZero::new)
-            .ok_or_else(|| io::Error::other("Height is > u32"))?;
+            .ok_or(BuildError::HeightOverflow { height })?;
         Ok(SortedRanges {
             included: self.included,
             excluded: self.excluded,
@@ -252,20 +399,17 @@ where
         })
     }
 }
-fn create_checked<T>(start: u64, end: u64) -> Result<T, io::Error>
-where
-    T: TryFrom<u64, Error: Display>,
-{
-    if end <= start {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("end ({end}) must be > start ({start})"),
-        ));
-    }
-    T::try_from(end - start).map_err(invalid_data)
-}
 
 impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
+    /// Mask without any pixel
+    pub fn empty(bounds: Rect<u32>) -> Self {
+        Self {
+            included: Vec::new(),
+            excluded: Vec::new(),
+            bounds,
+        }
+    }
+
     pub fn new<TRange>(r: NonZeroRange<TRange>, bounds: Rect<u32>) -> Self
     where
         TRange: UncheckedCast<TIncluded> + UncheckedCast<TExcluded> + Sub<Output = TRange>,
@@ -281,7 +425,7 @@ impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
     }
 
     /// Collects
-    pub fn try_from_ordered_iter<TIter>(iter: TIter) -> Result<Self, io::Error>
+    pub fn try_from_ordered_iter<TIter>(iter: TIter) -> Result<Self, BuildError>
     where
         TIter: IntoIterator<
                 Item: CreateRange<Item: TryInto<u64, Error: Display>>,
@@ -295,33 +439,67 @@ impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
         Self::try_from_ordered_iter_roi_internal(iter).and_then(|x| x.build_global(width))
     }
 
+    /// Collects ranges relative to the ROI `bounds`. Fails, if ranges are unordered, touch or
+    /// exceed the pixels of the ROI
     pub fn try_from_ordered_iter_roi<TIter>(
         iter: TIter,
         bounds: Rect<u32>,
-    ) -> Result<Self, io::Error>
+    ) -> Result<Self, BuildError>
     where
         TIter: IntoIterator<Item: CreateRange<Item: TryInto<u64, Error: Display>>>,
         TIncluded: TryFrom<u64, Error: Display>,
         TExcluded: TryFrom<u64, Error: Display>,
     {
-        Self::try_from_ordered_iter_roi_internal(iter).map(|r| r.build(bounds))
+        Self::try_from_ordered_iter_roi_internal(iter)?.build(bounds)
+    }
+    /// Like `try_from_ordered_iter_roi`, but gaps and lengths exceeding `TExcluded::MAX` or
+    /// `TIncluded::MAX` are split instead of failing: A zero length continues the previous gap,
+    /// a zero gap continues the previous length. Iterators merge these entries transparently,
+    /// so small types can represent any mask, while typical masks stay compact.
+    /// `len()` counts the stored entries including continuations
+    /// ```
+    /// use std::{num::NonZero, ops::Range};
+    /// use imask::{Rect, SortedRanges};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let size = NonZero::new(1000).unwrap();
+    /// let bounds = Rect::new(0, 0, size, size);
+    /// let ranges = SortedRanges::<u8, u8>::try_from_ordered_iter_roi_split([10u32..20, 600..1500], bounds)?;
+    /// assert_eq!(
+    ///     vec![10u64..20, 600..1500],
+    ///     ranges.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn try_from_ordered_iter_roi_split<TIter>(
+        iter: TIter,
+        bounds: Rect<u32>,
+    ) -> Result<Self, BuildError>
+    where
+        TIter: IntoIterator<Item: CreateRange<Item: TryInto<u64, Error: Display>>>,
+        TIncluded: TryFrom<u64, Error: Display> + num_traits::Bounded + UncheckedCast<u64>,
+        TExcluded: TryFrom<u64, Error: Display> + num_traits::Bounded + UncheckedCast<u64>,
+        u64: UncheckedCast<TIncluded> + UncheckedCast<TExcluded>,
+    {
+        let iter = iter.into_iter();
+        let mut builder = Builder::with_capacity(iter.size_hint().0);
+        for x in iter {
+            builder.add_split(x)?;
+        }
+        builder.build(bounds)
     }
+
     fn try_from_ordered_iter_roi_internal<TIter>(
         iter: TIter,
-    ) -> Result<Builder<TIncluded, TExcluded>, io::Error>
+    ) -> Result<Builder<TIncluded, TExcluded>, BuildError>
     where
         TIter: IntoIterator<Item: CreateRange<Item: TryInto<u64, Error: Display>>>,
         TIncluded: TryFrom<u64, Error: Display>,
         TExcluded: TryFrom<u64, Error: Display>,
     {
-        let mut iter = iter.into_iter();
-        let Some(first_range) = iter.next() else {
-            return Err(io::Error::new(
-                io::ErrorKind::UnexpectedEof,
-                "Requires at least one item",
-            ));
-        };
-        let mut builder = Builder::new(first_range, iter.size_hint().0 + 1)?;
+        let iter = iter.into_iter();
+        let mut builder = Builder::with_capacity(iter.size_hint().0);
 
         for x in iter {
             builder.add(x)?;
@@ -330,16 +508,18 @@ impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
         Ok(builder)
     }
 
-    /// Returns the number of ranges
-    #[allow(clippy::len_without_is_empty, reason = "Cannot be empty")]
+    /// Returns the number of ranges. Continuation entries of split encodings count separately
     pub fn len(&self) -> usize {
         self.included.len()
     }
 
-    // Returns the number of ranges
-    pub fn len_nonzero(&self) -> NonZero<usize> {
+    /// Returns the number of ranges or None, if the mask is empty
+    pub fn len_nonzero(&self) -> Option<NonZero<usize>> {
         NonZero::new(self.included.len())
-            .expect("Constructors make sure, there is always at least one Range")
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.included.is_empty()
     }
 
     pub fn iter_roi<T: CreateRange>(
@@ -378,6 +558,8 @@ impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
             self.bounds.height,
         )
     }
+    /// Ranges in the coordinates of a parent image with `width` columns.
+    /// See `SortedRangesIterGlobal`
     pub fn iter_global_with<T: CreateRange>(
         &self,
         width: NonZeroU32,
@@ -403,9 +585,8 @@ impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
         SortedRangesIterGlobal::new(
             self.included.iter().copied(),
             self.excluded.iter().copied(),
-            self.bounds.width,
+            self.bounds,
             width,
-            NonZeroU32::new(self.bounds.height.get() + self.bounds.y).unwrap(),
         )
     }
     pub fn iter_global_owned_with<T: CreateRange>(
@@ -429,9 +610,8 @@ impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
         SortedRangesIterGlobal::new(
             self.included.into_iter(),
             self.excluded.into_iter(),
-            self.bounds.width,
+            self.bounds,
             width,
-            NonZeroU32::new(self.bounds.height.get() + self.bounds.y).unwrap(),
         )
     }
 }
@@ -469,9 +649,7 @@ mod tests {
             .unwrap();
 
         let b_iter = b.iter_roi::<RangeInclusive<u64>>();
-        let a = a
-            .map_inplace(|a_iter| range_set_blaze_0_5::SortedDisjoint::union(b_iter, a_iter))
-            .unwrap();
+        let a = a.map_inplace(|a_iter| range_set_blaze_0_5::SortedDisjoint::union(b_iter, a_iter));
 
         assert_eq!(
             vec![10u64..40, 41..45],
@@ -498,14 +676,12 @@ mod tests {
         let a = SortedRanges::<u8, u8>::try_from_ordered_iter_roi([10u32..15, 30..35], TEST_BOUNDS)
             .unwrap();
 
-        let a = a
-            .map_inplace(|iter| {
-                iter.flat_map(|x| {
-                    let with_offset = (*x.start() + 10)..=(*x.end() + 10);
-                    [x, with_offset]
-                })
+        let a = a.map_inplace(|iter| {
+            iter.flat_map(|x| {
+                let with_offset = (*x.start() + 10)..=(*x.end() + 10);
+                [x, with_offset]
             })
-            .unwrap();
+        });
 
         assert_eq!(
             vec![10u64..15, 20..25, 30..35, 40..45],
@@ -514,13 +690,32 @@ mod tests {
     }
 
     #[test]
-    fn split_returns_none_when_empty() {
+    fn split_returns_empty_mask() {
         let a =
             SortedRanges::<u8, u8>::try_from_ordered_iter_roi([10u32..15], TEST_BOUNDS).unwrap();
 
         let result = a.map_inplace(|_| std::iter::empty());
 
-        assert!(result.is_none());
+        assert!(result.is_empty());
+        assert_eq!(SortedRanges::empty(TEST_BOUNDS), result);
+    }
+
+    #[test]
+    fn empty_iter_creates_empty_mask() {
+        let empty = SortedRanges::<u8, u8>::try_from_ordered_iter_roi(
+            std::iter::empty::<Range<u32>>(),
+            TEST_BOUNDS,
+        )
+        .unwrap();
+        assert_eq!(0, empty.len());
+        assert_eq!(None, empty.len_nonzero());
+        assert_eq!(TEST_BOUNDS, empty.bounds());
+        assert_eq!(0, empty.iter_roi::<Range<u64>>().count());
+        let global_width = NonZero::new(2000).unwrap();
+        assert_eq!(
+            0,
+            empty.iter_global_with::<Range<u64>>(global_width).count()
+        );
     }
 
     #[test]
@@ -549,20 +744,20 @@ mod tests {
         let error =
             SortedRanges::<u16, u8>::try_from_ordered_iter_roi([10u32..20, 276..280], TEST_BOUNDS)
                 .unwrap_err();
-        assert!(error.to_string().contains("out of range"), "{error}");
+        assert_eq!(BuildError::GapOverflow { gap: 256 }, error);
     }
 
     #[test]
     fn assert_big_ranges_cause_error() {
         let error = SortedRanges::<u8, u16>::try_from_ordered_iter_roi([10u32..280], TEST_BOUNDS)
             .unwrap_err();
-        assert!(error.to_string().contains("out of range"), "{error}");
+        assert_eq!(BuildError::LengthOverflow { len: 270 }, error);
     }
     #[test]
     fn zero_ranges_cause_error() {
         let error = SortedRanges::<u8, u8>::try_from_ordered_iter_roi([10u32..10], TEST_BOUNDS)
             .unwrap_err();
-        assert!(error.to_string().contains("must be >"), "{error}");
+        assert_eq!(BuildError::EmptyRange { start: 10, end: 10 }, error);
     }
 
     #[test]
@@ -570,7 +765,55 @@ mod tests {
         let error =
             SortedRanges::<u8, u8>::try_from_ordered_iter_roi([10u32..12, 11..12], TEST_BOUNDS)
                 .unwrap_err();
-        assert!(error.to_string().contains("must be >"), "{error}");
+        assert_eq!(
+            BuildError::Overlap {
+                start: 11,
+                previous_end: 12
+            },
+            error
+        );
+    }
+
+    #[test]
+    fn split_encoding_merges_continuations() {
+        let input = [10u32..20, 600..1500, 1800..1801];
+        let split =
+            SortedRanges::<u8, u8>::try_from_ordered_iter_roi_split(input.clone(), TEST_BOUNDS)
+                .unwrap();
+        let regular =
+            SortedRanges::<u16, u16>::try_from_ordered_iter_roi(input, TEST_BOUNDS).unwrap();
+        assert!(split.len() > regular.len());
+        assert_eq!(
+            regular.iter_roi::<Range<u64>>().collect::<Vec<_>>(),
+            split.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+        );
+        for width in [500, 1000, 2000] {
+            let width = NonZero::new(width).unwrap();
+            assert_eq!(
+                regular
+                    .iter_global_with::<Range<u64>>(width)
+                    .collect::<Vec<_>>(),
+                split
+                    .iter_global_with::<Range<u64>>(width)
+                    .collect::<Vec<_>>(),
+                "{width}"
+            );
+        }
+        let mut read = Vec::new();
+        split.map_inplace(|iter| {
+            read.extend(iter);
+            std::iter::empty()
+        });
+        assert_eq!(vec![10u64..=19, 600..=1499, 1800..=1800], read);
+    }
+
+    #[test]
+    fn split_encoding_without_overflow_is_regular() {
+        let input = [0u32..10, 20..30];
+        assert_eq!(
+            SortedRanges::<u8, u8>::try_from_ordered_iter_roi(input.clone(), TEST_BOUNDS),
+            SortedRanges::<u8, u8>::try_from_ordered_iter_roi_split(input, TEST_BOUNDS)
+        );
     }
 
     #[test]
@@ -684,9 +927,43 @@ mod tests {
     }
 }
 
-pub trait IntoRoiIterator {
-    type IntoIter: Iterator<Item = Self::Item>;
-    type Item: CreateRange;
+/// Converts a collection into sorted ranges relative to its ROI
+pub trait IntoRoiIterator<T: CreateRange> {
+    type IntoIter: Iterator<Item = T> + ImageDimension;
+
+    fn into_roi_iterator(self) -> Self::IntoIter;
+}
+
+impl<TIncluded, TExcluded, T> IntoRoiIterator<T> for SortedRanges<TIncluded, TExcluded>
+where
+    T: CreateRange<Item: Default + Copy + SignedNonZeroable + Add<Output = T::Item>>,
+    TIncluded: UncheckedCast<T::Item>,
+    TExcluded: UncheckedCast<T::Item>,
+{
+    type IntoIter =
+        WithRoi<SortedRangesIter<std::vec::IntoIter<TIncluded>, std::vec::IntoIter<TExcluded>, T>>;
 
-    fn into_roi_iterator() -> Self::IntoIter;
+    fn into_roi_iterator(self) -> Self::IntoIter {
+        let bounds = self.bounds;
+        WithRoi::new(self.iter_roi_owned(), bounds)
+    }
+}
+
+impl<'a, TIncluded, TExcluded, T> IntoRoiIterator<T> for &'a SortedRanges<TIncluded, TExcluded>
+where
+    T: CreateRange<Item: Default + Copy + SignedNonZeroable + Add<Output = T::Item>>,
+    TIncluded: UncheckedCast<T::Item>,
+    TExcluded: UncheckedCast<T::Item>,
+{
+    type IntoIter = WithRoi<
+        SortedRangesIter<
+            std::iter::Copied<std::slice::Iter<'a, TIncluded>>,
+            std::iter::Copied<std::slice::Iter<'a, TExcluded>>,
+            T,
+        >,
+    >;
+
+    fn into_roi_iterator(self) -> Self::IntoIter {
+        WithRoi::new(self.iter_roi(), self.bounds)
+    }
 }
@@ -1,21 +1,19 @@
 use std::collections::HashMap;
+use std::fmt::Display;
+use std::iter::FusedIterator;
+use std::num::NonZeroU32;
 use std::ops::Range;
 
-use nalgebra::{Matrix3, Vector3};
+use nalgebra::{Matrix3, Vector2, Vector3};
 
-use crate::CreateRange;
+use crate::{BuildError, CreateRange, ImageDimension, ImaskSet, Rect, SortedRanges, UncheckedCast};
 
 fn transform_point(m: &Matrix3<f64>, x: f64, y: f64) -> (f64, f64) {
     let v = m * Vector3::new(x, y, 1.0);
     (v[0], v[1])
 }
 
-fn quad_corners(
-    matrix: &Matrix3<f64>,
-    col: u64,
-    row: u64,
-    w: u64,
-) -> [(f64, f64); 4] {
+fn quad_corners(matrix: &Matrix3<f64>, col: u64, row: u64, w: u64) -> [(f64, f64); 4] {
     let left = col as f64 - 0.5;
     let right = col as f64 + w as f64 - 0.5;
     let top = row as f64 - 0.5;
@@ -28,11 +26,11 @@ fn quad_corners(
     ]
 }
 
-fn row_edges(corners: &[(f64, f64); 4], y: f64) -> Option<(f64, f64)> {
-    let mut xs = [0.0f64; 4];
-    let mut n = 0;
-    for i in 0..4 {
-        let j = (i + 1) % 4;
+/// Leftmost and rightmost intersection of the outline of a convex polygon with the row at `y`
+pub(super) fn row_edges(corners: &[(f64, f64)], y: f64) -> Option<(f64, f64)> {
+    let mut edges: Option<(f64, f64)> = None;
+    for i in 0..corners.len() {
+        let j = (i + 1) % corners.len();
         let (x0, y0) = corners[i];
         let (x1, y1) = corners[j];
         if y0 == y1 {
@@ -43,14 +41,10 @@ fn row_edges(corners: &[(f64, f64); 4], y: f64) -> Option<(f64, f64)> {
             continue;
         }
         let t = (y - y0) / (y1 - y0);
-        xs[n] = x0 + t * (x1 - x0);
-        n += 1;
-    }
-    if n < 2 {
-        return None;
+        let x = x0 + t * (x1 - x0);
+        edges = Some(edges.map_or((x, x), |(l, r)| (l.min(x), r.max(x))));
     }
-    xs[..n].sort_by(|a, b| a.partial_cmp(b).unwrap());
-    Some((xs[0], xs[n - 1]))
+    edges
 }
 
 fn rasterize_offsets(corners: &[(f64, f64); 4]) -> Vec<(i16, i16)> {
@@ -135,6 +129,7 @@ fn first_pixel(corners: &[(f64, f64); 4]) -> Option<(i32, i32)> {
 
 struct HeapEntry {
     current: u32,
+    source: u32,
     base_x: i32,
     base_y: i32,
     offset_idx: u32,
@@ -147,7 +142,7 @@ pub struct AffineTransformHeap {
     width: u32,
     height: u32,
     last_popped: Option<u32>,
-    pending: Option<u32>,
+    pending: Option<(u32, u32)>,
 }
 
 impl AffineTransformHeap {
@@ -157,12 +152,29 @@ impl AffineTransformHeap {
         R::Item: Into<u64>,
         I: Iterator<Item = R>,
     {
-        let img_w = width as u64;
+        Self::with_input_width(ranges, matrix, width as u64, width, height)
+    }
 
-        let mut segments: Vec<(u64, u64, u64)> = Vec::new();
+    /// Like `new`, but the input ranges are laid out in rows of `input_width`, which may differ
+    /// from the `width` of the output
+    fn with_input_width<R, I>(
+        ranges: I,
+        matrix: &Matrix3<f64>,
+        input_width: u64,
+        width: u32,
+        height: u32,
+    ) -> Self
+    where
+        R: CreateRange,
+        R::Item: Into<u64>,
+        I: Iterator<Item = R>,
+    {
+        let img_w = input_width;
+
+        let mut segments: Vec<(u64, u64, u64, u32)> = Vec::new();
         let mut max_width = 0u64;
 
-        for range in ranges {
+        for (source, range) in ranges.enumerate() {
             let start: u64 = range.start().into();
             let end: u64 = range.end().into();
 
@@ -175,7 +187,7 @@ impl AffineTransformHeap {
                 let seg_width = col_end_excl - col_start;
 
                 max_width = max_width.max(seg_width);
-                segments.push((col_start, row, seg_width));
+                segments.push((col_start, row, seg_width, source as u32));
                 pos = next_row;
             }
         }
@@ -186,7 +198,7 @@ impl AffineTransformHeap {
         let mut width_counts: HashMap<u64, u32> = HashMap::new();
         let mut entries: Vec<HeapEntry> = Vec::new();
 
-        for (col_start, row, seg_width) in segments {
+        for (col_start, row, seg_width, source) in segments {
             let corners = quad_corners(matrix, col_start, row, seg_width);
 
             let Some((base_x, base_y)) = first_pixel(&corners) else {
@@ -218,6 +230,7 @@ impl AffineTransformHeap {
             if let Some(current) = current {
                 entries.push(HeapEntry {
                     current,
+                    source,
                     base_x,
                     base_y,
                     offset_idx,
@@ -254,10 +267,10 @@ impl AffineTransformHeap {
             let left = 2 * i + 1;
             let right = 2 * i + 2;
             let mut smallest = i;
-            if left < n && self.heap[left].current < self.heap[smallest].current {
+            if left < n && self.heap[left].key() < self.heap[smallest].key() {
                 smallest = left;
             }
-            if right < n && self.heap[right].current < self.heap[smallest].current {
+            if right < n && self.heap[right].key() < self.heap[smallest].key() {
                 smallest = right;
             }
             if smallest == i {
@@ -291,9 +304,12 @@ impl AffineTransformHeap {
         false
     }
 
-    fn pop_pixel(&mut self) -> Option<u32> {
+    /// Returns the next pixel and the index of the input range it originates from.
+    /// If multiple ranges cover the same pixel, the first range wins
+    fn pop_pixel(&mut self) -> Option<(u32, u32)> {
         loop {
-            let result = self.heap.first()?.current;
+            let first = self.heap.first()?;
+            let (result, source) = (first.current, first.source);
 
             let advanced = {
                 let entry = &mut self.heap[0];
@@ -315,21 +331,18 @@ impl AffineTransformHeap {
                 continue;
             }
             self.last_popped = Some(result);
-            return Some(result);
+            return Some((result, source));
         }
     }
-}
-
-impl Iterator for AffineTransformHeap {
-    type Item = Range<u32>;
 
-    fn next(&mut self) -> Option<Range<u32>> {
-        let start = self.pending.take().or_else(|| self.pop_pixel())?;
+    /// Merges consecutive pixels. If `split_sources` is set, runs of different input ranges are kept apart
+    fn next_run(&mut self, split_sources: bool) -> Option<(Range<u32>, u32)> {
+        let (start, source) = self.pending.take().or_else(|| self.pop_pixel())?;
         let mut end = start + 1;
 
         loop {
             match self.pop_pixel() {
-                Some(p) if p == end => end = p + 1,
+                Some((p, s)) if p == end && (!split_sources || s == source) => end = p + 1,
                 Some(p) => {
                     self.pending = Some(p);
                     break;
@@ -338,7 +351,135 @@ impl Iterator for AffineTransformHeap {
             }
         }
 
-        Some(start..end)
+        Some((start..end, source))
+    }
+
+    /// Like `next`, but yields the index of the input range each output range originates from
+    pub(crate) fn next_with_source(&mut self) -> Option<(Range<u32>, u32)> {
+        self.next_run(true)
+    }
+}
+
+impl HeapEntry {
+    fn key(&self) -> (u32, u32) {
+        (self.current, self.source)
+    }
+}
+
+impl Iterator for AffineTransformHeap {
+    type Item = Range<u32>;
+
+    fn next(&mut self) -> Option<Range<u32>> {
+        self.next_run(false).map(|(range, _)| range)
+    }
+}
+
+/// Affine transform of a mask with known bounds. See `ImaskSet::affine_transform`
+pub struct AffineTransformIter {
+    heap: AffineTransformHeap,
+    bounds: Rect<u32>,
+}
+
+impl AffineTransformIter {
+    pub fn new<R, I>(ranges: I, matrix: &Matrix3<f64>, output_bounds: Rect<u32>) -> Self
+    where
+        R: CreateRange,
+        R::Item: Into<u64>,
+        I: Iterator<Item = R> + ImageDimension,
+    {
+        let input_bounds = ranges.bounds();
+        let matrix = roi_local_matrix(matrix, input_bounds, output_bounds);
+        let heap = AffineTransformHeap::with_input_width(
+            ranges,
+            &matrix,
+            input_bounds.width.get().into(),
+            output_bounds.width.get(),
+            output_bounds.height.get(),
+        );
+        Self {
+            heap,
+            bounds: output_bounds,
+        }
+    }
+
+    pub(crate) fn next_with_source(&mut self) -> Option<(Range<u32>, u32)> {
+        self.heap.next_with_source()
+    }
+}
+
+impl Iterator for AffineTransformIter {
+    type Item = Range<u32>;
+
+    fn next(&mut self) -> Option<Range<u32>> {
+        self.heap.next()
+    }
+}
+
+impl FusedIterator for AffineTransformIter {}
+
+impl ImageDimension for AffineTransformIter {
+    fn bounds(&self) -> Rect<u32> {
+        self.bounds
+    }
+    fn width(&self) -> NonZeroU32 {
+        self.bounds.width
+    }
+}
+
+/// `matrix` maps coordinates of the parent image. Ranges are relative to their ROI though,
+/// so the offset of the input is added before and the offset of the output removed afterwards
+pub(crate) fn roi_local_matrix(
+    matrix: &Matrix3<f64>,
+    input_bounds: Rect<u32>,
+    output_bounds: Rect<u32>,
+) -> Matrix3<f64> {
+    let to_global = Matrix3::new_translation(&Vector2::new(
+        f64::from(input_bounds.x),
+        f64::from(input_bounds.y),
+    ));
+    let to_output = Matrix3::new_translation(&Vector2::new(
+        -f64::from(output_bounds.x),
+        -f64::from(output_bounds.y),
+    ));
+    to_output * matrix * to_global
+}
+
+impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+{
+    /// Applies `matrix`, which maps pixel coordinates of the parent image, and collects all pixels
+    /// within `output_bounds`. See `ImaskSet::affine_transform`
+    /// ```
+    /// use std::{num::NonZero, ops::Range};
+    /// use imask::{ImageDimension, Rect, SortedRanges};
+    /// use nalgebra::{Matrix3, Vector2};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let size = NonZero::new(4).unwrap();
+    /// let ranges = SortedRanges::<u16, u16>::try_from_ordered_iter_roi(
+    ///     [0u32..2, 5..6],
+    ///     Rect::new(1, 1, size, size),
+    /// )?;
+    /// let shift = Matrix3::new_translation(&Vector2::new(2.0, 0.0));
+    /// let output = Rect::new(0, 0, NonZero::new(8).unwrap(), size);
+    /// let shifted = ranges.transform(&shift, output)?;
+    /// assert_eq!(output, shifted.bounds());
+    /// assert_eq!(vec![11u64..13, 20..21], shifted.iter_roi::<Range<u64>>().collect::<Vec<_>>());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn transform(
+        &self,
+        matrix: &Matrix3<f64>,
+        output_bounds: Rect<u32>,
+    ) -> Result<Self, BuildError> {
+        let transformed = self
+            .iter_roi::<Range<u64>>()
+            .with_roi(self.bounds)
+            .affine_transform(matrix, output_bounds);
+        Self::try_from_ordered_iter_roi(transformed, output_bounds)
     }
 }
 
@@ -347,7 +488,7 @@ mod tests {
     use super::*;
     use std::num::NonZero;
 
-    use crate::{ImaskSet, Rect};
+    use crate::{ImageDimension, ImaskSet, Rect};
 
     const W7: NonZero<u32> = NonZero::new(7).unwrap();
 
@@ -361,7 +502,13 @@ mod tests {
         eprintln!("\n{}:", label);
         for y in 0..h {
             let row: String = (0..w)
-                .map(|x| if bitmap[(y * w + x) as usize] { '#' } else { '.' })
+                .map(|x| {
+                    if bitmap[(y * w + x) as usize] {
+                        '#'
+                    } else {
+                        '.'
+                    }
+                })
                 .collect();
             eprintln!("  {}", row);
         }
@@ -369,13 +516,7 @@ mod tests {
 
     #[test]
     fn rotate_l_90deg_cw_about_center() {
-        let l_ranges: Vec<std::ops::Range<u32>> = vec![
-            0..1,
-            7..8,
-            14..15,
-            21..22,
-            28..34,
-        ];
+        let l_ranges: Vec<std::ops::Range<u32>> = vec![0..1, 7..8, 14..15, 21..22, 28..34];
 
         print_bitmap(7, 7, &l_ranges, "Input L-shape");
 
@@ -383,11 +524,7 @@ mod tests {
 
         let cx = 3.0_f64;
         let cy = 3.0_f64;
-        let matrix = Matrix3::new(
-            0.0, 1.0, cx - cy,
-            -1.0, 0.0, cx + cy,
-            0.0, 0.0, 1.0,
-        );
+        let matrix = Matrix3::new(0.0, 1.0, cx - cy, -1.0, 0.0, cx + cy, 0.0, 0.0, 1.0);
 
         let heap = AffineTransformHeap::new(ranges, &matrix, 7, 7);
         let result: Vec<Range<u32>> = heap.collect();
@@ -405,11 +542,7 @@ mod tests {
 
         let tx = 100.0_f64;
         let ty = 200.0_f64;
-        let matrix = Matrix3::new(
-            1.0, 0.0, tx,
-            0.0, 1.0, ty,
-            0.0, 0.0, 1.0,
-        );
+        let matrix = Matrix3::new(1.0, 0.0, tx, 0.0, 1.0, ty, 0.0, 0.0, 1.0);
 
         let heap = AffineTransformHeap::new(ranges, &matrix, 7, 7);
         let result: Vec<Range<u32>> = heap.collect();
@@ -425,9 +558,15 @@ mod tests {
         let cy = 3.0_f64;
         let scale = 2.0_f64;
         let matrix = Matrix3::new(
-            scale, 0.0, cx * (1.0 - scale),
-            0.0, scale, cy * (1.0 - scale),
-            0.0, 0.0, 1.0,
+            scale,
+            0.0,
+            cx * (1.0 - scale),
+            0.0,
+            scale,
+            cy * (1.0 - scale),
+            0.0,
+            0.0,
+            1.0,
         );
 
         let heap = AffineTransformHeap::new(ranges, &matrix, 7, 7);
@@ -437,10 +576,30 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn affine_transform_respects_rois() {
+        // 3x1 bar at (2, 1) of the parent image
+        let input = Rect::new(2u32, 1, NonZero::new(3).unwrap(), NonZero::new(1).unwrap());
+        let output = Rect::new(3u32, 2, NonZero::new(4).unwrap(), NonZero::new(2).unwrap());
+        let matrix = Matrix3::new_translation(&nalgebra::Vector2::new(0.0, 1.0));
+
+        let iter = std::iter::once(0u32..3)
+            .with_roi(input)
+            .affine_transform(&matrix, output);
+        assert_eq!(output, iter.bounds());
+        // Pixel (2, 2) is left of the output ROI
+        assert_eq!(vec![0..2], iter.collect::<Vec<_>>());
+    }
+
     #[test]
     fn rotate_20x20_square_30deg_sorted_disjoint() {
         let w50: NonZero<u32> = NonZero::new(50).unwrap();
-        let rect = Rect::new(15u32, 15, NonZero::new(20).unwrap(), NonZero::new(20).unwrap());
+        let rect = Rect::new(
+            15u32,
+            15,
+            NonZero::new(20).unwrap(),
+            NonZero::new(20).unwrap(),
+        );
         let ranges = rect.into_rect_iter::<std::ops::Range<u32>>(w50);
 
         let cx = 24.5_f64;
@@ -450,9 +609,15 @@ mod tests {
         let sin = angle.sin();
 
         let matrix = Matrix3::new(
-            cos, -sin, cx * (1.0 - cos) + cy * sin,
-            sin,  cos, cy * (1.0 - cos) - cx * sin,
-            0.0,  0.0, 1.0,
+            cos,
+            -sin,
+            cx * (1.0 - cos) + cy * sin,
+            sin,
+            cos,
+            cy * (1.0 - cos) - cx * sin,
+            0.0,
+            0.0,
+            1.0,
         );
 
         let w = 50u32;
@@ -481,8 +646,7 @@ mod tests {
             }
         }
 
-        let output_dir = std::env::var("CARGO_TARGET_DIR")
-            .unwrap_or_else(|_| "target".into());
+        let output_dir = std::env::var("CARGO_TARGET_DIR").unwrap_or_else(|_| "target".into());
         let path = format!("{}/rotate_30deg.png", output_dir);
         img.save(&path).unwrap();
 
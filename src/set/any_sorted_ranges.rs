@@ -0,0 +1,498 @@
+use std::{
+    fmt::{Debug, Display},
+    iter::FusedIterator,
+    num::{NonZero, NonZeroU32},
+    ops::{Add, Div, Mul, Rem, Sub},
+};
+
+use crate::{
+    BuildError, CreateRange, ImageDimension, Rect, SignedNonZeroable, SortedRanges,
+    SortedRangesIter, SortedRangesIterGlobal, UncheckedCast,
+};
+
+/// Unsigned integer type used to store included lengths or excluded gaps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StorageWidth {
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl StorageWidth {
+    /// Smallest width, which can store `value`
+    pub fn for_value(value: u64) -> Self {
+        if value <= u64::from(u8::MAX) {
+            Self::U8
+        } else if value <= u64::from(u16::MAX) {
+            Self::U16
+        } else if value <= u64::from(u32::MAX) {
+            Self::U32
+        } else {
+            Self::U64
+        }
+    }
+
+    pub fn max_value(self) -> u64 {
+        match self {
+            Self::U8 => u8::MAX.into(),
+            Self::U16 => u16::MAX.into(),
+            Self::U32 => u32::MAX.into(),
+            Self::U64 => u64::MAX,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum Values {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    U64(Vec<u64>),
+}
+
+impl Values {
+    /// Callers have to ensure, that all values fit into `width`
+    fn encode(values: impl ExactSizeIterator<Item = u64>, width: StorageWidth) -> Self {
+        match width {
+            StorageWidth::U8 => Self::U8(values.map(|v| v.cast_unchecked()).collect()),
+            StorageWidth::U16 => Self::U16(values.map(|v| v.cast_unchecked()).collect()),
+            StorageWidth::U32 => Self::U32(values.map(|v| v.cast_unchecked()).collect()),
+            StorageWidth::U64 => Self::U64(values.collect()),
+        }
+    }
+
+    fn encode_smallest<T: UncheckedCast<u64>>(values: &[T]) -> Self {
+        let widened = values.iter().map(|&v| v.cast_unchecked());
+        let max = widened.clone().max().unwrap_or_default();
+        Self::encode(widened, StorageWidth::for_value(max))
+    }
+
+    fn width(&self) -> StorageWidth {
+        match self {
+            Self::U8(_) => StorageWidth::U8,
+            Self::U16(_) => StorageWidth::U16,
+            Self::U32(_) => StorageWidth::U32,
+            Self::U64(_) => StorageWidth::U64,
+        }
+    }
+
+    fn max(&self) -> u64 {
+        self.iter().max().unwrap_or_default()
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::U8(v) => v.len(),
+            Self::U16(v) => v.len(),
+            Self::U32(v) => v.len(),
+            Self::U64(v) => v.len(),
+        }
+    }
+
+    fn iter(&self) -> ValuesIter<'_> {
+        match self {
+            Self::U8(v) => ValuesIter::U8(v.iter()),
+            Self::U16(v) => ValuesIter::U16(v.iter()),
+            Self::U32(v) => ValuesIter::U32(v.iter()),
+            Self::U64(v) => ValuesIter::U64(v.iter()),
+        }
+    }
+
+    fn into_iter(self) -> ValuesIntoIter {
+        match self {
+            Self::U8(v) => ValuesIntoIter::U8(v.into_iter()),
+            Self::U16(v) => ValuesIntoIter::U16(v.into_iter()),
+            Self::U32(v) => ValuesIntoIter::U32(v.into_iter()),
+            Self::U64(v) => ValuesIntoIter::U64(v.into_iter()),
+        }
+    }
+
+    fn try_convert<T: TryFrom<u64>>(
+        &self,
+        error: impl Fn(u64) -> BuildError,
+    ) -> Result<Vec<T>, BuildError> {
+        self.iter()
+            .map(|v| T::try_from(v).map_err(|_| error(v)))
+            .collect()
+    }
+}
+
+/// Values of `AnySortedRanges`, widened to u64
+#[derive(Clone)]
+pub enum ValuesIter<'a> {
+    U8(std::slice::Iter<'a, u8>),
+    U16(std::slice::Iter<'a, u16>),
+    U32(std::slice::Iter<'a, u32>),
+    U64(std::slice::Iter<'a, u64>),
+}
+
+impl Iterator for ValuesIter<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        match self {
+            Self::U8(i) => i.next().map(|&v| v.into()),
+            Self::U16(i) => i.next().map(|&v| v.into()),
+            Self::U32(i) => i.next().map(|&v| v.into()),
+            Self::U64(i) => i.next().copied(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Self::U8(i) => i.size_hint(),
+            Self::U16(i) => i.size_hint(),
+            Self::U32(i) => i.size_hint(),
+            Self::U64(i) => i.size_hint(),
+        }
+    }
+}
+
+impl ExactSizeIterator for ValuesIter<'_> {}
+impl FusedIterator for ValuesIter<'_> {}
+
+/// Owned values of `AnySortedRanges`, widened to u64
+#[derive(Clone)]
+pub enum ValuesIntoIter {
+    U8(std::vec::IntoIter<u8>),
+    U16(std::vec::IntoIter<u16>),
+    U32(std::vec::IntoIter<u32>),
+    U64(std::vec::IntoIter<u64>),
+}
+
+impl Iterator for ValuesIntoIter {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        match self {
+            Self::U8(i) => i.next().map(u64::from),
+            Self::U16(i) => i.next().map(u64::from),
+            Self::U32(i) => i.next().map(u64::from),
+            Self::U64(i) => i.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            Self::U8(i) => i.size_hint(),
+            Self::U16(i) => i.size_hint(),
+            Self::U32(i) => i.size_hint(),
+            Self::U64(i) => i.size_hint(),
+        }
+    }
+}
+
+impl ExactSizeIterator for ValuesIntoIter {}
+impl FusedIterator for ValuesIntoIter {}
+
+/// `SortedRanges`, which chooses the storage type of included lengths and excluded gaps at
+/// runtime. Constructors pick the smallest types, which fit all values.
+/// ```
+/// use std::{num::NonZero, ops::Range};
+/// use imask::{AnySortedRanges, Rect, StorageWidth};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let bounds = Rect::new(0, 0, NonZero::new(1000).unwrap(), NonZero::new(1000).unwrap());
+/// let ranges = AnySortedRanges::try_from_ordered_iter_roi([10u32..20, 100_000..100_010], bounds)?;
+/// assert_eq!(StorageWidth::U8, ranges.included_width());
+/// assert_eq!(StorageWidth::U32, ranges.excluded_width());
+/// assert_eq!(
+///     vec![10u64..20, 100_000..100_010],
+///     ranges.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct AnySortedRanges {
+    included: Values,
+    excluded: Values,
+    bounds: Rect<u32>,
+}
+
+impl Debug for AnySortedRanges {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnySortedRanges")
+            .field("range_count", &self.included.len())
+            .field("included_width", &self.included.width())
+            .field("excluded_width", &self.excluded.width())
+            .field("bounds", &self.bounds)
+            .finish()
+    }
+}
+
+impl AnySortedRanges {
+    /// Mask without any pixel
+    pub fn empty(bounds: Rect<u32>) -> Self {
+        SortedRanges::<u8, u8>::empty(bounds).into()
+    }
+
+    /// Like `SortedRanges::try_from_ordered_iter`
+    pub fn try_from_ordered_iter<TIter>(iter: TIter) -> Result<Self, BuildError>
+    where
+        TIter: IntoIterator<
+                Item: CreateRange<Item: TryInto<u64, Error: Display>>,
+                IntoIter: ImageDimension,
+            >,
+    {
+        SortedRanges::<u64, u64>::try_from_ordered_iter(iter).map(Self::from)
+    }
+
+    /// Like `SortedRanges::try_from_ordered_iter_roi`
+    pub fn try_from_ordered_iter_roi<TIter>(
+        iter: TIter,
+        bounds: Rect<u32>,
+    ) -> Result<Self, BuildError>
+    where
+        TIter: IntoIterator<Item: CreateRange<Item: TryInto<u64, Error: Display>>>,
+    {
+        SortedRanges::<u64, u64>::try_from_ordered_iter_roi(iter, bounds).map(Self::from)
+    }
+
+    pub fn included_width(&self) -> StorageWidth {
+        self.included.width()
+    }
+
+    pub fn excluded_width(&self) -> StorageWidth {
+        self.excluded.width()
+    }
+
+    /// Stores the values with other types. Fails, if a value doesn't fit
+    pub fn reencode(
+        &self,
+        included: StorageWidth,
+        excluded: StorageWidth,
+    ) -> Result<Self, BuildError> {
+        let len = self.included.max();
+        if len > included.max_value() {
+            return Err(BuildError::LengthOverflow { len });
+        }
+        let gap = self.excluded.max();
+        if gap > excluded.max_value() {
+            return Err(BuildError::GapOverflow { gap });
+        }
+        Ok(Self {
+            included: Values::encode(self.included.iter(), included),
+            excluded: Values::encode(self.excluded.iter(), excluded),
+            bounds: self.bounds,
+        })
+    }
+
+    /// Converts into statically typed `SortedRanges`. Fails, if a value doesn't fit
+    pub fn try_to_sorted_ranges<TIncluded, TExcluded>(
+        &self,
+    ) -> Result<SortedRanges<TIncluded, TExcluded>, BuildError>
+    where
+        TIncluded: TryFrom<u64>,
+        TExcluded: TryFrom<u64>,
+    {
+        Ok(SortedRanges {
+            included: self
+                .included
+                .try_convert(|len| BuildError::LengthOverflow { len })?,
+            excluded: self
+                .excluded
+                .try_convert(|gap| BuildError::GapOverflow { gap })?,
+            bounds: self.bounds,
+        })
+    }
+
+    /// Returns the number of ranges
+    pub fn len(&self) -> usize {
+        self.included.len()
+    }
+
+    /// Returns the number of ranges or None, if the mask is empty
+    pub fn len_nonzero(&self) -> Option<NonZero<usize>> {
+        NonZero::new(self.included.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.included.len() == 0
+    }
+
+    pub fn iter_roi<T: CreateRange>(&self) -> SortedRangesIter<ValuesIter<'_>, ValuesIter<'_>, T>
+    where
+        u64: UncheckedCast<T::Item>,
+        T::Item: Default + Copy + SignedNonZeroable + Add<Output = T::Item>,
+    {
+        SortedRangesIter::new(
+            self.included.iter(),
+            self.excluded.iter(),
+            T::Item::default(),
+            self.bounds.width,
+            self.bounds.height,
+        )
+    }
+
+    pub fn iter_roi_owned<T: CreateRange>(
+        self,
+    ) -> SortedRangesIter<ValuesIntoIter, ValuesIntoIter, T>
+    where
+        u64: UncheckedCast<T::Item>,
+        T::Item: Default + Copy + SignedNonZeroable + Add<Output = T::Item>,
+    {
+        SortedRangesIter::new(
+            self.included.into_iter(),
+            self.excluded.into_iter(),
+            T::Item::default(),
+            self.bounds.width,
+            self.bounds.height,
+        )
+    }
+
+    pub fn iter_global_with<T: CreateRange>(
+        &self,
+        width: NonZeroU32,
+    ) -> SortedRangesIterGlobal<ValuesIter<'_>, ValuesIter<'_>, T>
+    where
+        u64: UncheckedCast<T::Item>,
+        T::Item: Default
+            + Copy
+            + SignedNonZeroable
+            + Add<Output = T::Item>
+            + Sub<Output = T::Item>
+            + Mul<Output = T::Item>
+            + Div<Output = T::Item>
+            + Rem<Output = T::Item>
+            + Ord,
+        u32: UncheckedCast<T::Item>,
+    {
+        SortedRangesIterGlobal::new(
+            self.included.iter(),
+            self.excluded.iter(),
+            self.bounds,
+            width,
+        )
+    }
+
+    pub fn iter_global_owned_with<T: CreateRange>(
+        self,
+        width: NonZeroU32,
+    ) -> SortedRangesIterGlobal<ValuesIntoIter, ValuesIntoIter, T>
+    where
+        u64: UncheckedCast<T::Item>,
+        T::Item: Default
+            + Copy
+            + SignedNonZeroable
+            + Add<Output = T::Item>
+            + Sub<Output = T::Item>
+            + Mul<Output = T::Item>
+            + Div<Output = T::Item>
+            + Rem<Output = T::Item>
+            + Ord,
+        u32: UncheckedCast<T::Item>,
+    {
+        SortedRangesIterGlobal::new(
+            self.included.into_iter(),
+            self.excluded.into_iter(),
+            self.bounds,
+            width,
+        )
+    }
+}
+
+/// Re-encodes with the smallest types, which fit all values
+impl<TIncluded, TExcluded> From<SortedRanges<TIncluded, TExcluded>> for AnySortedRanges
+where
+    TIncluded: UncheckedCast<u64>,
+    TExcluded: UncheckedCast<u64>,
+{
+    fn from(value: SortedRanges<TIncluded, TExcluded>) -> Self {
+        Self {
+            included: Values::encode_smallest(&value.included),
+            excluded: Values::encode_smallest(&value.excluded),
+            bounds: value.bounds,
+        }
+    }
+}
+
+impl ImageDimension for AnySortedRanges {
+    fn bounds(&self) -> Rect<u32> {
+        self.bounds
+    }
+    fn width(&self) -> NonZero<u32> {
+        self.bounds.width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::*;
+
+    const SIZE: NonZeroU32 = NonZero::new(1000).unwrap();
+    const BOUNDS: Rect<u32> = Rect::new(0, 0, SIZE, SIZE);
+
+    #[test]
+    fn picks_smallest_widths() {
+        let ranges =
+            AnySortedRanges::try_from_ordered_iter_roi([0u32..300, 400..401], BOUNDS).unwrap();
+        assert_eq!(StorageWidth::U16, ranges.included_width());
+        assert_eq!(StorageWidth::U8, ranges.excluded_width());
+
+        let empty = AnySortedRanges::empty(BOUNDS);
+        assert!(empty.is_empty());
+        assert_eq!(StorageWidth::U8, empty.included_width());
+    }
+
+    #[test]
+    fn iterates_like_sorted_ranges() {
+        let input = [5u32..10, 999..1003, 70_000..71_000];
+        let typed =
+            SortedRanges::<u32, u32>::try_from_ordered_iter_roi(input.clone(), BOUNDS).unwrap();
+        let any = AnySortedRanges::from(typed.clone());
+        assert_eq!(
+            typed.iter_roi::<Range<u32>>().collect::<Vec<_>>(),
+            any.iter_roi::<Range<u32>>().collect::<Vec<_>>()
+        );
+        let width = NonZero::new(1200).unwrap();
+        assert_eq!(
+            typed
+                .iter_global_with::<Range<u64>>(width)
+                .collect::<Vec<_>>(),
+            any.clone()
+                .iter_global_owned_with::<Range<u64>>(width)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn reencode_widens_and_narrows() {
+        let ranges =
+            AnySortedRanges::try_from_ordered_iter_roi([10u32..20, 400..410], BOUNDS).unwrap();
+        let wide = ranges
+            .reencode(StorageWidth::U64, StorageWidth::U32)
+            .unwrap();
+        assert_eq!(StorageWidth::U64, wide.included_width());
+        assert_eq!(
+            ranges.iter_roi::<Range<u64>>().collect::<Vec<_>>(),
+            wide.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Err(BuildError::GapOverflow { gap: 380 }),
+            wide.reencode(StorageWidth::U8, StorageWidth::U8)
+        );
+        assert_eq!(
+            Ok(ranges),
+            wide.reencode(StorageWidth::U8, StorageWidth::U16)
+        );
+    }
+
+    #[test]
+    fn convert_to_sorted_ranges() {
+        let ranges =
+            AnySortedRanges::try_from_ordered_iter_roi([10u32..20, 400..410], BOUNDS).unwrap();
+        let typed = ranges.try_to_sorted_ranges::<u8, u16>().unwrap();
+        assert_eq!(
+            vec![10u64..20, 400..410],
+            typed.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Err(BuildError::GapOverflow { gap: 380 }),
+            ranges.try_to_sorted_ranges::<u8, u8>()
+        );
+    }
+}
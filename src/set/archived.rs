@@ -0,0 +1,171 @@
+use std::{
+    iter::FusedIterator,
+    num::{NonZero, NonZeroU32},
+    ops::{Add, Div, Mul, Rem, Sub},
+};
+
+use rkyv::Archive;
+
+use crate::{
+    ArchivedSortedRanges, CreateRange, ImageDimension, Rect, SignedNonZeroable, SortedRangesIter,
+    SortedRangesIterGlobal, UncheckedCast,
+};
+
+/// Converts endian-aware archived integers into `T` while iterating
+pub struct ArchivedValuesIter<'a, T: Archive>(std::slice::Iter<'a, T::Archived>);
+
+impl<'a, T: Archive> ArchivedValuesIter<'a, T> {
+    pub(crate) fn new(values: &'a [T::Archived]) -> Self {
+        Self(values.iter())
+    }
+}
+
+impl<T: Archive> Clone for ArchivedValuesIter<'_, T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Iterator for ArchivedValuesIter<'_, T>
+where
+    T: Archive<Archived: Copy> + From<T::Archived>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.0.next().map(|&v| v.into())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for ArchivedValuesIter<'_, T> where
+    T: Archive<Archived: Copy> + From<T::Archived>
+{
+}
+impl<T> FusedIterator for ArchivedValuesIter<'_, T> where
+    T: Archive<Archived: Copy> + From<T::Archived>
+{
+}
+
+/// Same API as `SortedRanges`, but works directly on the archive without deserializing
+impl<TIncluded: Archive, TExcluded: Archive> ArchivedSortedRanges<TIncluded, TExcluded> {
+    /// Returns the number of ranges. Continuation entries of split encodings count separately
+    pub fn len(&self) -> usize {
+        self.included.len()
+    }
+
+    /// Returns the number of ranges or None, if the mask is empty
+    pub fn len_nonzero(&self) -> Option<NonZero<usize>> {
+        NonZero::new(self.included.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.included.is_empty()
+    }
+
+    pub fn iter_roi<T: CreateRange>(
+        &self,
+    ) -> SortedRangesIter<ArchivedValuesIter<'_, TIncluded>, ArchivedValuesIter<'_, TExcluded>, T>
+    where
+        TIncluded: UncheckedCast<T::Item> + From<TIncluded::Archived>,
+        TExcluded: UncheckedCast<T::Item> + From<TExcluded::Archived>,
+        TIncluded::Archived: Copy,
+        TExcluded::Archived: Copy,
+        T::Item: Default + Copy + SignedNonZeroable + Add<Output = T::Item>,
+    {
+        let bounds = self.bounds();
+        SortedRangesIter::new(
+            ArchivedValuesIter::new(&self.included),
+            ArchivedValuesIter::new(&self.excluded),
+            T::Item::default(),
+            bounds.width,
+            bounds.height,
+        )
+    }
+
+    pub fn iter_global_with<T: CreateRange>(
+        &self,
+        width: NonZeroU32,
+    ) -> SortedRangesIterGlobal<
+        ArchivedValuesIter<'_, TIncluded>,
+        ArchivedValuesIter<'_, TExcluded>,
+        T,
+    >
+    where
+        TIncluded: UncheckedCast<T::Item> + From<TIncluded::Archived>,
+        TExcluded: UncheckedCast<T::Item> + From<TExcluded::Archived>,
+        TIncluded::Archived: Copy,
+        TExcluded::Archived: Copy,
+        T::Item: Default
+            + Copy
+            + SignedNonZeroable
+            + Add<Output = T::Item>
+            + Sub<Output = T::Item>
+            + Mul<Output = T::Item>
+            + Div<Output = T::Item>
+            + Rem<Output = T::Item>
+            + Ord,
+        u32: UncheckedCast<T::Item>,
+    {
+        let bounds = self.bounds();
+        SortedRangesIterGlobal::new(
+            ArchivedValuesIter::new(&self.included),
+            ArchivedValuesIter::new(&self.excluded),
+            bounds,
+            width,
+        )
+    }
+}
+
+impl<TIncluded: Archive, TExcluded: Archive> ImageDimension
+    for ArchivedSortedRanges<TIncluded, TExcluded>
+{
+    fn bounds(&self) -> Rect<u32> {
+        self.bounds.to_native()
+    }
+    fn width(&self) -> NonZero<u32> {
+        self.bounds.width.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use crate::SortedRanges;
+
+    use super::*;
+
+    #[test]
+    fn iterate_archive_without_deserializing() {
+        let size = NonZero::new(1000).unwrap();
+        let bounds = Rect::new(0, 10, size, size);
+        let ranges = SortedRanges::<u16, u32>::try_from_ordered_iter_roi(
+            [10u32..20, 70_000..70_500],
+            bounds,
+        )
+        .unwrap();
+        let bytes = rkyv::to_bytes::<rkyv::rancor::Error>(&ranges).unwrap();
+        let archived =
+            rkyv::access::<ArchivedSortedRanges<u16, u32>, rkyv::rancor::Error>(&bytes).unwrap();
+
+        assert_eq!(bounds, archived.bounds());
+        assert_eq!(2, archived.len());
+        assert_eq!(
+            ranges.iter_roi::<Range<u64>>().collect::<Vec<_>>(),
+            archived.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+        );
+        let width = NonZero::new(1500).unwrap();
+        assert_eq!(
+            ranges
+                .iter_global_with::<Range<u64>>(width)
+                .collect::<Vec<_>>(),
+            archived
+                .iter_global_with::<Range<u64>>(width)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,8 +1,8 @@
 use std::{cell::RefCell, marker::PhantomData, num::NonZero, rc::Rc};
 
-use crate::{CreateRange, ImageDimension, SignedNonZeroable, UncheckedCast};
+use crate::{CreateRange, ImageDimension, UncheckedCast};
 
-/// result of
+/// Result of `ImaskSet::chunk_by_row_lending`
 pub struct ChunkByRowRanges<T: Iterator, R> {
     shared: Rc<RefCell<Shared<T>>>,
     range: PhantomData<R>,
@@ -89,20 +89,24 @@ where
     R::Item: Copy + Ord + std::ops::Sub<Output = R::Item>,
 {
     fn drop(&mut self) {
+        // Skip the rest of the row, if it wasn't consumed completely
         let mut shared = self.shared.borrow_mut();
-        if self.pending.take().is_some() && shared.pending_nextline.is_none() {
-            for r in &mut shared.source {
-                if r.start() >= self.next_line_start {
-                    shared.pending_nextline = Some(r);
-                    break;
-                } else if r.end() > self.next_line_start {
-                    let remaining_len = r.end() - self.next_line_start;
-                    shared.pending_nextline = Some(R::new_debug_checked_zeroable(
-                        self.next_line_start,
-                        remaining_len,
-                    ));
-                    break;
-                }
+        if shared.pending_nextline.is_some() {
+            return;
+        }
+        let rest = self.pending.take().into_iter();
+        let Shared {
+            source,
+            pending_nextline,
+        } = &mut *shared;
+        for r in rest.chain(source) {
+            if r.start() >= self.next_line_start {
+                *pending_nextline = Some(r);
+                break;
+            } else if r.end() > self.next_line_start {
+                *pending_nextline =
+                    Some(R::new_debug_checked_zeroable(self.next_line_start, r.end()));
+                break;
             }
         }
     }
@@ -118,6 +122,10 @@ where
 
     fn next(&mut self) -> Option<Self::Item> {
         let mut shared = self.shared.borrow_mut();
+        if self.pending.is_none() && shared.pending_nextline.is_some() {
+            // The row is complete
+            return None;
+        }
         let range = self.pending.take().or_else(|| {
             shared.source.next().and_then(|r| {
                 if r.start() >= self.next_line_start {
@@ -129,17 +137,14 @@ where
         })?;
 
         if range.end() > self.next_line_start {
-            let remaining_len = unsafe {
-                SignedNonZeroable::create_non_zero_unchecked(range.end() - self.next_line_start)
-            };
-
-            shared.pending_nextline =
-                Some(R::new_debug_checked(self.next_line_start, remaining_len));
-
-            let clip_len = unsafe {
-                SignedNonZeroable::create_non_zero_unchecked(self.next_line_start - range.start())
-            };
-            Some(R::new_debug_checked(range.start(), clip_len))
+            shared.pending_nextline = Some(R::new_debug_checked_zeroable(
+                self.next_line_start,
+                range.end(),
+            ));
+            Some(R::new_debug_checked_zeroable(
+                range.start(),
+                self.next_line_start,
+            ))
         } else {
             Some(range)
         }
@@ -149,7 +154,7 @@ where
 impl<T, R> ImageDimension for ChunkByRowRanges<T, R>
 where
     T: Iterator<Item = R> + ImageDimension,
-    R: CreateRange<Item: SignedNonZeroable>,
+    R: CreateRange,
 {
     fn width(&self) -> NonZero<u32> {
         self.shared.borrow().source.width()
@@ -174,7 +179,90 @@ where
     }
 }
 
+/// Result of `ImaskSet::rows`
+pub struct RowsIter<T: Iterator, R> {
+    inner: ChunkByRowRanges<T, R>,
+}
+
+impl<T: Iterator, R: CreateRange> RowsIter<T, R> {
+    pub(crate) fn new(source: T) -> Self {
+        Self {
+            inner: ChunkByRowRanges::new(source),
+        }
+    }
+}
+
+impl<T, R> Iterator for RowsIter<T, R>
+where
+    T: Iterator<Item = R> + ImageDimension,
+    R: CreateRange,
+    R::Item: Copy
+        + Ord
+        + std::ops::Add<Output = R::Item>
+        + std::ops::Sub<Output = R::Item>
+        + std::ops::Div<Output = R::Item>
+        + std::ops::Mul<Output = R::Item>,
+    u32: UncheckedCast<R::Item>,
+{
+    type Item = (R::Item, RowRangesIter<T, R>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (row, inner) = self.inner.next()?;
+        let width: R::Item = self.inner.width().get().cast_unchecked();
+        Some((
+            row,
+            RowRangesIter {
+                inner,
+                row_start: row * width,
+            },
+        ))
+    }
+}
+
+impl<T, R> ImageDimension for RowsIter<T, R>
+where
+    T: Iterator<Item = R> + ImageDimension,
+    R: CreateRange,
+{
+    fn width(&self) -> NonZero<u32> {
+        self.inner.width()
+    }
+
+    fn bounds(&self) -> crate::Rect<u32> {
+        self.inner.bounds()
+    }
+}
+
+/// Ranges of a single row as x-ranges relative to the ROI
+pub struct RowRangesIter<T, R>
+where
+    T: Iterator<Item = R>,
+    R: CreateRange,
+    R::Item: Copy + Ord + std::ops::Sub<Output = R::Item>,
+{
+    inner: ChunkByRowRangesRowIter<T, R>,
+    row_start: R::Item,
+}
+
+impl<T, R> Iterator for RowRangesIter<T, R>
+where
+    T: Iterator<Item = R>,
+    R: CreateRange,
+    R::Item: Copy + Ord + std::ops::Sub<Output = R::Item>,
+{
+    type Item = R;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let range = self.inner.next()?;
+        Some(R::new_debug_checked_zeroable(
+            range.start() - self.row_start,
+            range.end() - self.row_start,
+        ))
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
 mod tests {
     use std::{num::NonZero, ops::Range};
 
@@ -218,4 +306,40 @@ mod tests {
             .collect::<Vec<_>>();
         assert_eq!(sums, vec!((1, 9)));
     }
+    #[test]
+    fn drop_partially_consumed_row() {
+        let source = [0usize..3, 4..5, 6..11, 12..20, 25..26].with_bounds(WIDTH_U32, WIDTH_U32);
+        let firsts = source
+            .chunk_by_row_lending()
+            .map(|(row, mut i)| (row, i.next()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            firsts,
+            vec![(0, Some(0..3)), (1, Some(10..11)), (2, Some(25..26))]
+        );
+    }
+    #[test]
+    fn exhausted_row_stays_exhausted() {
+        let source = [0usize..3, 12..14, 25..26].with_bounds(WIDTH_U32, WIDTH_U32);
+        let mut chunked = source.chunk_by_row_lending();
+        let (_, mut row) = chunked.next().unwrap();
+        assert_eq!(row.by_ref().collect::<Vec<_>>(), vec![0..3]);
+        assert_eq!(row.next(), None);
+        drop(row);
+        let (row, ranges) = chunked.next().unwrap();
+        assert_eq!((1, vec![12..14]), (row, ranges.collect::<Vec<_>>()));
+    }
+    #[test]
+    fn rows_with_roi_offset() {
+        let roi = crate::Rect::new(3, 4, WIDTH_U32, WIDTH_U32);
+        let rows = [5u32..25]
+            .with_roi(roi)
+            .rows()
+            .map(|(y, xs)| (y, xs.collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![(0, vec![5..10]), (1, vec![0..10]), (2, vec![0..5])]
+        );
+    }
 }
@@ -0,0 +1,410 @@
+use std::{
+    fmt::Display,
+    iter::FusedIterator,
+    num::{NonZero, NonZeroU32},
+    ops::Range,
+};
+
+use crate::{
+    BuildError, CreateRange, ImageDimension, Rect, SortedRanges, SortedRangesIter, UncheckedCast,
+    build_error::check_bounds,
+};
+
+use super::edit::EndIndex;
+
+type CopiedSliceIter<'a, T> = std::iter::Copied<std::slice::Iter<'a, T>>;
+type BandIter<'a, TIncluded, TExcluded> =
+    SortedRangesIter<CopiedSliceIter<'a, TIncluded>, CopiedSliceIter<'a, TExcluded>, Range<u64>>;
+
+/// Mask, which stores a `SortedRanges` per band of `band_height` rows. Edits only re-encode the
+/// affected bands, which keeps them cheap for huge images (e.g. whole-slide images).
+/// Each band keeps the end of its ranges, so edits find the affected ranges by binary search.
+/// Iterating yields the same ranges as the equivalent flat `SortedRanges`
+/// ```
+/// use std::{num::NonZero, ops::Range};
+/// use imask::{ChunkedSortedRanges, ImageDimension, Rect};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let size = NonZero::new(100_000).unwrap();
+/// let bounds = Rect::new(0, 0, size, size);
+/// let mut mask = ChunkedSortedRanges::<u32, u32>::empty(bounds, NonZero::new(256).unwrap());
+/// mask.insert_range(1_000..2_000)?;
+/// mask.insert_range(9_999_000_000..9_999_000_010)?;
+/// assert_eq!(
+///     vec![1_000u64..2_000, 9_999_000_000..9_999_000_010],
+///     mask.iter_roi().collect::<Vec<_>>()
+/// );
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkedSortedRanges<TIncluded, TExcluded> {
+    bands: Vec<SortedRanges<TIncluded, TExcluded>>,
+    /// `EndIndex` of each band
+    indices: Vec<EndIndex>,
+    band_height: NonZeroU32,
+    bounds: Rect<u32>,
+}
+
+impl<TIncluded, TExcluded> ChunkedSortedRanges<TIncluded, TExcluded> {
+    /// Mask without any pixel
+    pub fn empty(bounds: Rect<u32>, band_height: NonZeroU32) -> Self {
+        let band_count = bounds.height.get().div_ceil(band_height.get());
+        let bands = (0..band_count)
+            .map(|i| {
+                let top = i * band_height.get();
+                let rows = band_height.get().min(bounds.height.get() - top);
+                SortedRanges::empty(Rect::new(
+                    bounds.x,
+                    bounds.y + top,
+                    bounds.width,
+                    NonZero::new(rows).expect("Bands are within bounds"),
+                ))
+            })
+            .collect();
+        Self {
+            bands,
+            indices: vec![EndIndex::default(); band_count as usize],
+            band_height,
+            bounds,
+        }
+    }
+
+    /// Like `SortedRanges::try_from_ordered_iter_roi`, but distributes the ranges into bands
+    pub fn try_from_ordered_iter_roi<TIter>(
+        iter: TIter,
+        bounds: Rect<u32>,
+        band_height: NonZeroU32,
+    ) -> Result<Self, BuildError>
+    where
+        TIter: IntoIterator<Item: CreateRange<Item: Into<u64>>>,
+        TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+        TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    {
+        let mut result = Self::empty(bounds, band_height);
+        let mut band_ranges = vec![Vec::new(); result.bands.len()];
+        let mut previous_end = None;
+        for range in iter {
+            let (start, end) = (range.start().into(), range.end().into());
+            if let Some(previous_end) = previous_end
+                && start <= previous_end
+            {
+                return Err(BuildError::Overlap {
+                    start,
+                    previous_end,
+                });
+            }
+            if end <= start {
+                return Err(BuildError::EmptyRange { start, end });
+            }
+            check_bounds(end, bounds)?;
+            for (band, local) in result.split(start..end) {
+                band_ranges[band].push(local);
+            }
+            previous_end = Some(end);
+        }
+        for ((band, index), ranges) in result
+            .bands
+            .iter_mut()
+            .zip(&mut result.indices)
+            .zip(band_ranges)
+        {
+            *band = SortedRanges::try_from_ordered_iter_roi(ranges, band.bounds)?;
+            *index = EndIndex::new(band);
+        }
+        Ok(result)
+    }
+
+    pub fn from_sorted_ranges(
+        ranges: &SortedRanges<TIncluded, TExcluded>,
+        band_height: NonZeroU32,
+    ) -> Result<Self, BuildError>
+    where
+        TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+        TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    {
+        Self::try_from_ordered_iter_roi(ranges.iter_roi::<Range<u64>>(), ranges.bounds, band_height)
+    }
+
+    /// Merges all bands. Ranges crossing band borders are joined again
+    pub fn to_sorted_ranges(&self) -> Result<SortedRanges<TIncluded, TExcluded>, BuildError>
+    where
+        TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+        TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    {
+        SortedRanges::try_from_ordered_iter_roi(self.iter_roi(), self.bounds)
+    }
+
+    pub fn band_height(&self) -> NonZeroU32 {
+        self.band_height
+    }
+
+    /// Band masks from top to bottom. Their bounds are relative to the parent image
+    pub fn bands(&self) -> &[SortedRanges<TIncluded, TExcluded>] {
+        &self.bands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bands.iter().all(SortedRanges::is_empty)
+    }
+
+    /// Number of pixels per band
+    fn band_size(&self) -> u64 {
+        u64::from(self.band_height.get()) * u64::from(self.bounds.width.get())
+    }
+
+    /// Splits a ROI-local range into band-local ranges
+    fn split(
+        &self,
+        range: Range<u64>,
+    ) -> impl Iterator<Item = (usize, Range<u64>)> + use<TIncluded, TExcluded> {
+        let band_size = self.band_size();
+        let (first, last) = (range.start / band_size, (range.end - 1) / band_size);
+        (first..=last).map(move |band| {
+            let offset = band * band_size;
+            let local =
+                range.start.max(offset) - offset..range.end.min(offset + band_size) - offset;
+            (band as usize, local)
+        })
+    }
+
+    /// Ranges relative to the ROI
+    pub fn iter_roi(&self) -> ChunkedSortedRangesIter<'_, TIncluded, TExcluded>
+    where
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+    {
+        ChunkedSortedRangesIter {
+            bands: self.bands.iter(),
+            band_size: self.band_size(),
+            band_offset: 0,
+            current: None,
+            pending: None,
+            bounds: self.bounds,
+        }
+    }
+}
+
+impl<TIncluded, TExcluded> ChunkedSortedRanges<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64>,
+{
+    /// See `SortedRanges::insert_range`. Only bands overlapping `range` are modified.
+    /// All bands are encoded before any is modified, so on error the mask remains unchanged
+    pub fn insert_range(&mut self, range: Range<u64>) -> Result<(), BuildError> {
+        self.edit(range, true)
+    }
+
+    /// See `SortedRanges::remove_range` and `insert_range`
+    pub fn remove_range(&mut self, range: Range<u64>) -> Result<(), BuildError> {
+        self.edit(range, false)
+    }
+
+    /// Inserts all ranges. If an error occurs, the ranges before the failing one remain inserted
+    pub fn union_in_place(
+        &mut self,
+        ranges: impl IntoIterator<Item = Range<u64>>,
+    ) -> Result<(), BuildError> {
+        ranges.into_iter().try_for_each(|r| self.insert_range(r))
+    }
+
+    /// Removes all ranges. See `union_in_place`
+    pub fn subtract_in_place(
+        &mut self,
+        ranges: impl IntoIterator<Item = Range<u64>>,
+    ) -> Result<(), BuildError> {
+        ranges.into_iter().try_for_each(|r| self.remove_range(r))
+    }
+
+    fn edit(&mut self, range: Range<u64>, include: bool) -> Result<(), BuildError> {
+        if range.is_empty() {
+            return Ok(());
+        }
+        check_bounds(range.end, self.bounds)?;
+        let mut splices = Vec::new();
+        for (band, local) in self.split(range) {
+            let ranges = &self.bands[band];
+            if let Some(splice) = ranges.plan_splice(local, include, Some(&self.indices[band]))? {
+                splices.push((band, splice));
+            }
+        }
+        for (band, splice) in splices {
+            self.bands[band].apply_splice(splice, Some(&mut self.indices[band]));
+        }
+        Ok(())
+    }
+}
+
+impl<TIncluded, TExcluded> ImageDimension for ChunkedSortedRanges<TIncluded, TExcluded> {
+    fn bounds(&self) -> Rect<u32> {
+        self.bounds
+    }
+    fn width(&self) -> NonZero<u32> {
+        self.bounds.width
+    }
+}
+
+/// Iterates all bands of a `ChunkedSortedRanges` and joins ranges crossing band borders
+pub struct ChunkedSortedRangesIter<'a, TIncluded, TExcluded> {
+    bands: std::slice::Iter<'a, SortedRanges<TIncluded, TExcluded>>,
+    band_size: u64,
+    /// ROI offset of the band after `current`
+    band_offset: u64,
+    /// ROI offset and ranges of the current band
+    current: Option<(u64, BandIter<'a, TIncluded, TExcluded>)>,
+    pending: Option<Range<u64>>,
+    bounds: Rect<u32>,
+}
+
+impl<TIncluded, TExcluded> ChunkedSortedRangesIter<'_, TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64>,
+    TExcluded: UncheckedCast<u64>,
+{
+    fn next_unmerged(&mut self) -> Option<Range<u64>> {
+        loop {
+            if let Some((offset, iter)) = self.current.as_mut()
+                && let Some(r) = iter.next()
+            {
+                return Some(r.start + *offset..r.end + *offset);
+            }
+            let band = self.bands.next()?;
+            self.current = Some((self.band_offset, band.iter_roi()));
+            self.band_offset += self.band_size;
+        }
+    }
+}
+
+impl<TIncluded, TExcluded> Iterator for ChunkedSortedRangesIter<'_, TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64>,
+    TExcluded: UncheckedCast<u64>,
+{
+    type Item = Range<u64>;
+
+    fn next(&mut self) -> Option<Range<u64>> {
+        while let Some(range) = self.next_unmerged() {
+            match self.pending.as_mut() {
+                Some(pending) if pending.end == range.start => pending.end = range.end,
+                _ => {
+                    if let Some(pending) = self.pending.replace(range) {
+                        return Some(pending);
+                    }
+                }
+            }
+        }
+        self.pending.take()
+    }
+}
+
+impl<TIncluded, TExcluded> FusedIterator for ChunkedSortedRangesIter<'_, TIncluded, TExcluded> where
+    Self: Iterator
+{
+}
+
+impl<TIncluded, TExcluded> ImageDimension for ChunkedSortedRangesIter<'_, TIncluded, TExcluded> {
+    fn bounds(&self) -> Rect<u32> {
+        Rect::new(0, 0, self.bounds.width, self.bounds.height)
+    }
+    fn width(&self) -> NonZero<u32> {
+        self.bounds.width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: NonZeroU32 = NonZero::new(10).unwrap();
+    const BOUNDS: Rect<u32> = Rect::new(5, 5, WIDTH, NonZero::new(7).unwrap());
+    const BAND_HEIGHT: NonZeroU32 = NonZero::new(3).unwrap();
+
+    fn flat() -> SortedRanges<u8, u8> {
+        SortedRanges::try_from_ordered_iter_roi([5u32..12, 25..35, 58..70], BOUNDS).unwrap()
+    }
+
+    #[test]
+    fn roundtrip_with_flat_ranges() {
+        let flat = flat();
+        let chunked = ChunkedSortedRanges::from_sorted_ranges(&flat, BAND_HEIGHT).unwrap();
+        assert_eq!(3, chunked.bands().len());
+        assert_eq!(
+            Rect::new(5, 11, WIDTH, NonZero::new(1).unwrap()),
+            chunked.bands()[2].bounds()
+        );
+        // 25..35 and 58..70 cross the band borders at 30 and 60
+        assert_eq!(
+            vec![0..10],
+            chunked.bands()[2]
+                .iter_roi::<Range<u64>>()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            flat.iter_roi::<Range<u64>>().collect::<Vec<_>>(),
+            chunked.iter_roi().collect::<Vec<_>>()
+        );
+        assert_eq!(flat, chunked.to_sorted_ranges().unwrap());
+    }
+
+    #[test]
+    fn edits_match_flat_edits() {
+        let mut flat = flat();
+        let mut chunked = ChunkedSortedRanges::from_sorted_ranges(&flat, BAND_HEIGHT).unwrap();
+        let inserted = [0..3, 12..25, 40..62];
+        let removed = [1..2, 28..31, 59..68];
+        flat.union_in_place(inserted.clone()).unwrap();
+        chunked.union_in_place(inserted).unwrap();
+        flat.subtract_in_place(removed.clone()).unwrap();
+        chunked.subtract_in_place(removed).unwrap();
+        assert_eq!(flat, chunked.to_sorted_ranges().unwrap());
+
+        chunked.remove_range(0..70).unwrap();
+        assert!(chunked.is_empty());
+        assert_eq!(
+            Err(BuildError::OutOfBounds { end: 71, size: 70 }),
+            chunked.insert_range(0..71)
+        );
+    }
+
+    #[test]
+    fn reject_unordered_input() {
+        let error = ChunkedSortedRanges::<u8, u8>::try_from_ordered_iter_roi(
+            [5u32..12, 12..14],
+            BOUNDS,
+            BAND_HEIGHT,
+        )
+        .unwrap_err();
+        assert_eq!(
+            BuildError::Overlap {
+                start: 12,
+                previous_end: 12
+            },
+            error
+        );
+    }
+
+    #[test]
+    fn failing_edit_keeps_all_bands() {
+        let bounds = Rect::new(0, 0, NonZero::new(200).unwrap(), NonZero::new(4).unwrap());
+        let mut chunked = ChunkedSortedRanges::<u8, u8>::try_from_ordered_iter_roi(
+            [200u32..210, 385..395, 410..420, 600..610, 750..760],
+            bounds,
+            NonZero::new(2).unwrap(),
+        )
+        .unwrap();
+        let before = chunked.clone();
+        // The first band could be edited, but the offset of 750..760 in the second band would be
+        // 350 > u8::MAX
+        assert_eq!(
+            Err(BuildError::GapOverflow { gap: 350 }),
+            chunked.remove_range(390..610)
+        );
+        assert_eq!(before, chunked);
+        chunked.remove_range(390..420).unwrap();
+        assert_eq!(
+            vec![200..210, 385..390, 600..610, 750..760],
+            chunked.iter_roi().collect::<Vec<_>>()
+        );
+    }
+}
@@ -56,6 +56,81 @@ where
     }
 }
 
+/// Position of a range relative to the ROI
+pub(crate) enum Clipped<N> {
+    /// The range and all following ranges are below the ROI
+    Below,
+    /// No pixel of the range is inside the ROI
+    Outside,
+    /// Start and end relative to the ROI
+    Inside(N, N),
+}
+
+/// Clips a range with the pixels `start..end` of an image with `outer_w` columns to `roi`.
+/// The result is contiguous, as rows between the first and last row are cut to the ROI width
+pub(crate) fn clip_range<N>(start: N, end: N, outer_w: N, roi: Rect<u32>) -> Clipped<N>
+where
+    N: Copy
+        + Ord
+        + One
+        + std::ops::Sub<Output = N>
+        + std::ops::Add<Output = N>
+        + std::ops::Mul<Output = N>
+        + std::ops::Div<Output = N>
+        + std::ops::Rem<Output = N>,
+    u32: UncheckedCast<N>,
+{
+    let sub_w: N = roi.width.get().cast_unchecked();
+    let sub_x: N = roi.x.cast_unchecked();
+    let sub_y: N = roi.y.cast_unchecked();
+    let sub_h: N = roi.height.get().cast_unchecked();
+    let sub_col_end = sub_x + sub_w;
+    let sub_row_end = sub_y + sub_h;
+
+    let first_row = start / outer_w;
+    let last_row = (end - N::one()) / outer_w;
+    if first_row >= sub_row_end {
+        return Clipped::Below;
+    }
+    if last_row < sub_y {
+        return Clipped::Outside;
+    }
+
+    let (mut first_row, mut first_col) = if first_row < sub_y {
+        (sub_y, sub_x)
+    } else {
+        (first_row, (start % outer_w).max(sub_x))
+    };
+    // Starts right of the ROI: Continue at the next row
+    if first_col >= sub_col_end {
+        first_row = first_row + N::one();
+        first_col = sub_x;
+    }
+    let (mut last_row, mut last_col) = if last_row >= sub_row_end {
+        (sub_row_end - N::one(), sub_col_end - N::one())
+    } else {
+        (
+            last_row,
+            ((end - N::one()) % outer_w).min(sub_col_end - N::one()),
+        )
+    };
+    // Ends left of the ROI: Stop at the end of the previous row
+    if last_col < sub_x {
+        if last_row <= first_row {
+            return Clipped::Outside;
+        }
+        last_row = last_row - N::one();
+        last_col = sub_col_end - N::one();
+    }
+    if first_row > last_row || (first_row == last_row && first_col > last_col) {
+        return Clipped::Outside;
+    }
+
+    let sub_start = (first_row - sub_y) * sub_w + first_col - sub_x;
+    let sub_end = (last_row - sub_y) * sub_w + last_col - sub_x + N::one();
+    Clipped::Inside(sub_start, sub_end)
+}
+
 impl<T, R> Iterator for Clip2dIter<T, R>
 where
     T: Iterator<Item = R> + ImageDimension,
@@ -77,64 +152,19 @@ where
 
     fn next(&mut self) -> Option<Self::Item> {
         let outer_w: R::Item = self.parent.width().get().cast_unchecked();
-        let sub_w: R::Item = self.roi.width.get().cast_unchecked();
-        let sub_x = self.roi.x.cast_unchecked();
-        let sub_y = self.roi.y.cast_unchecked();
-        let sub_h: R::Item = self.roi.height.get().cast_unchecked();
-
-        let sub_row_start = sub_y;
-        let sub_row_end = sub_y + sub_h;
-        let sub_col_start = sub_x;
-        let sub_col_end = sub_x + sub_w;
 
         loop {
             let Some(item) = self.parent.next() else {
                 return self.pending.take();
             };
-            let start = item.start();
-            let end = item.end();
-
-            let first_row = start / outer_w;
-            let last_row = (end - R::Item::one()) / outer_w;
-            let first_col = start % outer_w;
-            let last_col = (end - R::Item::one()) % outer_w;
-
-            if first_row >= sub_row_end {
-                return self.pending.take();
-            }
-            if last_row < sub_row_start {
-                continue;
-            }
-
-            let clipped_first_row = first_row.max(sub_row_start);
-            let clipped_last_row = last_row.min(sub_row_end - R::Item::one());
-
-            let clipped_first_col = if clipped_first_row == first_row {
-                first_col.max(sub_col_start)
-            } else {
-                sub_col_start
-            };
-
-            let clipped_last_col = if clipped_last_row == last_row {
-                last_col.min(sub_col_end - R::Item::one())
-            } else {
-                sub_col_end - R::Item::one()
+            let (sub_start, sub_end) = match clip_range(item.start(), item.end(), outer_w, self.roi)
+            {
+                Clipped::Below => return self.pending.take(),
+                Clipped::Outside => continue,
+                Clipped::Inside(start, end) => (start, end),
             };
 
-            if clipped_first_col >= sub_col_end || clipped_last_col < sub_col_start {
-                continue;
-            }
-
-            let sub_first_row = clipped_first_row - sub_y;
-            let sub_last_row = clipped_last_row - sub_y;
-            let sub_first_col = clipped_first_col - sub_x;
-            let sub_last_col = clipped_last_col - sub_x;
-
-            let sub_start = sub_first_row * sub_w + sub_first_col;
-            let sub_end = sub_last_row * sub_w + sub_last_col + R::Item::one();
-
             debug_assert!(sub_start < sub_end, "Input must be SortedDisjoint");
-            //let is_line_end = (sub_end % outer_w) == R::Item::zero();
 
             match self.pending.take() {
                 Some(x) => {
@@ -183,6 +213,64 @@ where
     }
 }
 
+/// Like `Clip2dIter`, but for `(range, meta)` items. Each item keeps its meta, so touching ranges
+/// aren't merged
+pub struct Clip2dMetaIter<T> {
+    parent: T,
+    roi: Rect<u32>,
+}
+
+impl<T: ImageDimension> Clip2dMetaIter<T> {
+    pub fn try_new(parent: T, roi: Rect<u32>) -> Result<Self, RoiWidthExceedsOriginal> {
+        let Clip2dIter { parent, roi, .. } = Clip2dIter::<T, ()>::try_new(parent, roi)?;
+        Ok(Self { parent, roi })
+    }
+}
+
+impl<T, R, M> Iterator for Clip2dMetaIter<T>
+where
+    T: Iterator<Item = (R, M)> + ImageDimension,
+    R: CreateRange<
+        Item: Copy
+                  + Ord
+                  + One
+                  + std::ops::Sub<Output = R::Item>
+                  + std::ops::Add<Output = R::Item>
+                  + std::ops::Mul<Output = R::Item>
+                  + std::ops::Div<Output = R::Item>
+                  + std::ops::Rem<Output = R::Item>,
+    >,
+    u32: UncheckedCast<R::Item>,
+{
+    type Item = (R, M);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let outer_w: R::Item = self.parent.width().get().cast_unchecked();
+        loop {
+            let (range, meta) = self.parent.next()?;
+            match clip_range(range.start(), range.end(), outer_w, self.roi) {
+                Clipped::Below => return None,
+                Clipped::Outside => continue,
+                Clipped::Inside(start, end) => {
+                    return Some((R::new_debug_checked_zeroable(start, end), meta));
+                }
+            }
+        }
+    }
+}
+
+impl<T: FusedIterator> FusedIterator for Clip2dMetaIter<T> where Self: Iterator {}
+
+impl<T> ImageDimension for Clip2dMetaIter<T> {
+    fn width(&self) -> NonZero<u32> {
+        self.roi.width
+    }
+
+    fn bounds(&self) -> Rect<u32> {
+        self.roi
+    }
+}
+
 #[cfg(feature = "range-set-blaze-0_5")]
 mod range_set_blaze_impl {
     use range_set_blaze_0_5::{Integer, SortedDisjoint, SortedStarts};
@@ -412,6 +500,45 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn range_starting_right_of_roi_keeps_following_rows() -> TestResult {
+        let roi = Rect::new(2, 0, NonZero::new(3).unwrap(), NonZero::new(3).unwrap());
+        // Starts at column 8 of row 0 and ends at column 1 of row 2
+        let source = [0..1usize, 8..22].with_bounds(WIDTH_U32, WIDTH_U32);
+        let result: Vec<_> = source.try_clip_2d(roi)?.collect();
+        assert_eq!(result, vec![3..6]);
+        Ok(())
+    }
+
+    #[test]
+    fn range_ending_left_of_roi_keeps_previous_rows() -> TestResult {
+        let roi = Rect::new(2, 0, NonZero::new(3).unwrap(), NonZero::new(3).unwrap());
+        // Starts at column 3 of row 0 and ends at column 0 of row 2
+        let source = [3..21usize, 25..26].with_bounds(WIDTH_U32, WIDTH_U32);
+        let result: Vec<_> = source.try_clip_2d(roi)?.collect();
+        assert_eq!(result, vec![1..6]);
+        Ok(())
+    }
+
+    #[test]
+    fn meta_is_kept_and_touching_ranges_stay_separate() -> TestResult {
+        let roi = Rect::new(2, 1, NonZero::new(4).unwrap(), NonZero::new(2).unwrap());
+        let source = [
+            (0..13usize, 'a'),
+            (13..15, 'b'),
+            (17..25, 'c'),
+            (25..40, 'd'),
+        ]
+        .with_bounds(WIDTH_U32, WIDTH_U32);
+        let clipped = source.try_clip_2d_meta(roi)?;
+        assert_eq!(roi, clipped.bounds());
+        assert_eq!(
+            vec![(0..1, 'a'), (1..3, 'b'), (4..7, 'c'), (7..8, 'd')],
+            clipped.collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
     // Not yet supported... Might not be worth because of the performance penalty for storing pending items
     // This could be implemented zero-cost, if width is a interna of the iterators (we'd just decrement width until x+new_width = old_width)
     // #[test]
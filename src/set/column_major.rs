@@ -0,0 +1,159 @@
+use std::{fmt::Display, num::NonZero, ops::Range};
+
+use crate::{
+    BuildError, CreateRange, ImageDimension, Rect, SignedNonZeroable, SortedRanges,
+    SortedRangesIter, UncheckedCast,
+};
+
+/// Mask stored column by column. Ranges are vertical runs with the offset `x * height + y` within
+/// the ROI, so this is the row-major mask of the transposed image. `ImageDimension` describes the
+/// transposed ROI, while `row_major_bounds` returns the ROI of the original mask.
+///
+/// Conversions sweep over the rows and keep track of active vertical runs (see
+/// `SortedRanges::transpose`), so no dense bitmap is built
+/// ```
+/// use std::{num::NonZero, ops::Range};
+/// use imask::{ColumnMajorRanges, ImageDimension, Rect, SortedRanges};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let roi = Rect::new(1, 2, NonZero::new(3).unwrap(), NonZero::new(2).unwrap());
+/// // ##.
+/// // .#.
+/// let mask = SortedRanges::<u16, u16>::try_from_ordered_iter_roi([0u32..2, 4..5], roi)?;
+/// let columns = ColumnMajorRanges::from_row_major(&mask)?;
+/// assert_eq!(Rect::new(2, 1, NonZero::new(2).unwrap(), NonZero::new(3).unwrap()), columns.bounds());
+/// assert_eq!(vec![0u64..1, 2..4], columns.iter_columns::<Range<u64>>().collect::<Vec<_>>());
+/// assert_eq!(vec![1, 2, 0], columns.column_projection());
+/// assert_eq!(mask, columns.to_row_major()?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMajorRanges<TIncluded, TExcluded> {
+    transposed: SortedRanges<TIncluded, TExcluded>,
+}
+
+impl<TIncluded, TExcluded> ColumnMajorRanges<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+{
+    pub fn from_row_major(mask: &SortedRanges<TIncluded, TExcluded>) -> Result<Self, BuildError> {
+        Ok(Self {
+            transposed: mask.transpose()?,
+        })
+    }
+
+    pub fn to_row_major(&self) -> Result<SortedRanges<TIncluded, TExcluded>, BuildError> {
+        self.transposed.transpose()
+    }
+}
+
+impl<TIncluded, TExcluded> ColumnMajorRanges<TIncluded, TExcluded> {
+    /// Wraps a mask, which already is in column-major order, e.g. from COCO RLE
+    pub fn from_transposed(transposed: SortedRanges<TIncluded, TExcluded>) -> Self {
+        Self { transposed }
+    }
+
+    /// Row-major mask of the transposed image
+    pub fn as_transposed(&self) -> &SortedRanges<TIncluded, TExcluded> {
+        &self.transposed
+    }
+
+    pub fn into_transposed(self) -> SortedRanges<TIncluded, TExcluded> {
+        self.transposed
+    }
+
+    /// ROI of the mask in its original orientation
+    pub fn row_major_bounds(&self) -> Rect<u32> {
+        let bounds = self.transposed.bounds;
+        Rect::new(bounds.y, bounds.x, bounds.height, bounds.width)
+    }
+
+    /// Vertical runs relative to the ROI, ordered by column
+    pub fn iter_columns<T: CreateRange>(
+        &self,
+    ) -> SortedRangesIter<
+        std::iter::Copied<std::slice::Iter<'_, TIncluded>>,
+        std::iter::Copied<std::slice::Iter<'_, TExcluded>>,
+        T,
+    >
+    where
+        TIncluded: UncheckedCast<T::Item>,
+        TExcluded: UncheckedCast<T::Item>,
+        T::Item: Default + Copy + SignedNonZeroable + std::ops::Add<Output = T::Item>,
+    {
+        self.transposed.iter_roi()
+    }
+
+    /// Number of set pixels per column of the ROI
+    pub fn column_projection(&self) -> Vec<u64>
+    where
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+    {
+        let bounds = self.transposed.bounds;
+        let height = u64::from(bounds.width.get());
+        let mut counts = vec![0; bounds.height.get() as usize];
+        for Range { mut start, end } in self.iter_columns::<Range<u64>>() {
+            while start < end {
+                let column = start / height;
+                let column_end = end.min((column + 1) * height);
+                counts[column as usize] += column_end - start;
+                start = column_end;
+            }
+        }
+        counts
+    }
+}
+
+impl<TIncluded, TExcluded> ImageDimension for ColumnMajorRanges<TIncluded, TExcluded> {
+    fn bounds(&self) -> Rect<u32> {
+        self.transposed.bounds
+    }
+
+    fn width(&self) -> NonZero<u32> {
+        self.transposed.bounds.width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// ROI (2, 1, 4x3)
+    /// ```text
+    /// ####
+    /// ..##
+    /// ####
+    /// ```
+    fn sample() -> SortedRanges<u8, u8> {
+        let roi = Rect::new(2, 1, NonZero::new(4).unwrap(), NonZero::new(3).unwrap());
+        SortedRanges::try_from_ordered_iter_roi([0u32..4, 6..12], roi).unwrap()
+    }
+
+    #[test]
+    fn runs_cross_columns() {
+        let columns = ColumnMajorRanges::from_row_major(&sample()).unwrap();
+        assert_eq!(
+            Rect::new(1, 2, NonZero::new(3).unwrap(), NonZero::new(4).unwrap()),
+            columns.bounds()
+        );
+        assert_eq!(sample().bounds(), columns.row_major_bounds());
+        // Columns 2 and 3 are full, so their runs are merged
+        assert_eq!(
+            vec![0u64..1, 2..4, 5..12],
+            columns.iter_columns::<Range<u64>>().collect::<Vec<_>>()
+        );
+        assert_eq!(vec![2, 2, 3, 3], columns.column_projection());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mask = sample();
+        let columns = ColumnMajorRanges::from_row_major(&mask).unwrap();
+        assert_eq!(mask, columns.to_row_major().unwrap());
+        let wrapped = ColumnMajorRanges::from_transposed(columns.clone().into_transposed());
+        assert_eq!(columns, wrapped);
+    }
+}
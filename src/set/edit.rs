@@ -0,0 +1,470 @@
+use std::ops::Range;
+
+use crate::{
+    BuildError, SortedRanges, UncheckedCast,
+    build_error::{check_bounds, checked_gap, checked_len, checked_offset},
+};
+
+/// Absolute end of each encoded range. Edits find the affected ranges by binary search and
+/// patch the index afterwards, instead of decoding the mask from the start
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct EndIndex(Vec<u64>);
+
+impl EndIndex {
+    pub(crate) fn new<TIncluded, TExcluded>(ranges: &SortedRanges<TIncluded, TExcluded>) -> Self
+    where
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+    {
+        let mut end = 0;
+        let ends = ranges
+            .included
+            .iter()
+            .zip(&ranges.excluded)
+            .map(|(&i, &e)| {
+                end += e.cast_unchecked() + i.cast_unchecked();
+                end
+            });
+        Self(ends.collect())
+    }
+}
+
+/// Re-encoded ranges, which replace the `entries` of a mask. Encoding is validated while planning,
+/// so applying a splice can't fail
+pub(crate) struct Splice<TIncluded, TExcluded> {
+    entries: Range<usize>,
+    included: Vec<TIncluded>,
+    excluded: Vec<TExcluded>,
+    ends: Vec<u64>,
+    /// New gap of the range after `entries`
+    next_gap: Option<TExcluded>,
+}
+
+impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64>,
+{
+    /// Adds all pixels of `range` (relative to the ROI). Touching or overlapping ranges are merged.
+    /// Empty ranges are ignored. On error, the mask remains unchanged.
+    /// Only ranges around `range` are re-encoded, but finding them scans the mask. Use `editor`
+    /// for many edits. Masks with split encoding (see `try_from_ordered_iter_roi_split`) are
+    /// rejected with `BuildError::SplitEncoding`
+    /// ```
+    /// use std::{num::NonZero, ops::Range};
+    /// use imask::{Rect, SortedRanges};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let size = NonZero::new(10).unwrap();
+    /// let mut ranges = SortedRanges::<u8, u8>::try_from_ordered_iter_roi([2u32..4, 8..9], Rect::new(0, 0, size, size))?;
+    /// ranges.insert_range(4..6)?;
+    /// ranges.remove_range(3..5)?;
+    /// assert_eq!(vec![2u64..3, 5..6, 8..9], ranges.iter_roi::<Range<u64>>().collect::<Vec<_>>());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn insert_range(&mut self, range: Range<u64>) -> Result<(), BuildError> {
+        self.check_not_split()?;
+        self.edit(range, true, None)
+    }
+
+    /// Removes all pixels of `range` (relative to the ROI). See `insert_range`
+    pub fn remove_range(&mut self, range: Range<u64>) -> Result<(), BuildError> {
+        self.check_not_split()?;
+        self.edit(range, false, None)
+    }
+
+    /// Inserts all ranges using a `SortedRangesEditor`.
+    /// If an error occurs, the ranges before the failing one remain inserted
+    pub fn union_in_place(
+        &mut self,
+        ranges: impl IntoIterator<Item = Range<u64>>,
+    ) -> Result<(), BuildError> {
+        self.editor()?.union_in_place(ranges)
+    }
+
+    /// Removes all ranges. See `union_in_place`
+    pub fn subtract_in_place(
+        &mut self,
+        ranges: impl IntoIterator<Item = Range<u64>>,
+    ) -> Result<(), BuildError> {
+        self.editor()?.subtract_in_place(ranges)
+    }
+
+    /// Indexes the mask for many edits, e.g. all stamps of a brush stroke. See `SortedRangesEditor`.
+    /// Fails for masks with split encoding
+    pub fn editor(&mut self) -> Result<SortedRangesEditor<'_, TIncluded, TExcluded>, BuildError> {
+        self.check_not_split()?;
+        Ok(SortedRangesEditor {
+            index: EndIndex::new(self),
+            ranges: self,
+        })
+    }
+
+    /// Edits re-encode single entries, so continuation entries of a split encoding would be
+    /// misread as ranges. The first gap is the offset, which may be zero
+    fn check_not_split(&self) -> Result<(), BuildError> {
+        let zero_len = self.included.iter().any(|&i| i.cast_unchecked() == 0);
+        let zero_gap = self
+            .excluded
+            .iter()
+            .skip(1)
+            .any(|&e| e.cast_unchecked() == 0);
+        if zero_len || zero_gap {
+            return Err(BuildError::SplitEncoding);
+        }
+        Ok(())
+    }
+
+    fn edit(
+        &mut self,
+        region: Range<u64>,
+        include: bool,
+        index: Option<&mut EndIndex>,
+    ) -> Result<(), BuildError> {
+        if let Some(splice) = self.plan_splice(region, include, index.as_deref())? {
+            self.apply_splice(splice, index);
+        }
+        Ok(())
+    }
+
+    fn start_at(&self, index: usize, prev_end: u64) -> u64 {
+        prev_end + self.excluded[index].cast_unchecked()
+    }
+
+    fn end_at(&self, index: usize, start: u64) -> u64 {
+        start + self.included[index].cast_unchecked()
+    }
+
+    /// Encodes the ranges, which set all pixels within `region` to `include`. Returns None, if
+    /// the mask doesn't change. Without `index`, the affected ranges are found by a scan
+    pub(crate) fn plan_splice(
+        &self,
+        region: Range<u64>,
+        include: bool,
+        index: Option<&EndIndex>,
+    ) -> Result<Option<Splice<TIncluded, TExcluded>>, BuildError> {
+        if region.is_empty() {
+            return Ok(None);
+        }
+        check_bounds(region.end, self.bounds)?;
+
+        // Skip ranges before the region. Touching ranges are merged when including
+        let before = |end: u64| end < region.start || (!include && end == region.start);
+        let len = self.included.len();
+        let (first, prev_end) = match index {
+            Some(EndIndex(ends)) => {
+                let first = ends.partition_point(|&end| before(end));
+                (first, first.checked_sub(1).map_or(0, |i| ends[i]))
+            }
+            None => {
+                let (mut first, mut prev_end) = (0, 0);
+                while first < len {
+                    let end = self.end_at(first, self.start_at(first, prev_end));
+                    if !before(end) {
+                        break;
+                    }
+                    prev_end = end;
+                    first += 1;
+                }
+                (first, prev_end)
+            }
+        };
+
+        // Ranges within the region
+        let mut last = first;
+        let mut window: Option<Range<u64>> = None;
+        let mut pos = prev_end;
+        let mut next_start = None;
+        while last < len {
+            let start = self.start_at(last, pos);
+            if start > region.end || (!include && start == region.end) {
+                next_start = Some(start);
+                break;
+            }
+            pos = self.end_at(last, start);
+            window = Some(window.map_or(start, |w| w.start)..pos);
+            last += 1;
+        }
+
+        let mut replacement = Vec::with_capacity(2);
+        match (include, window) {
+            (true, None) => replacement.push(region),
+            (true, Some(w)) => replacement.push(w.start.min(region.start)..w.end.max(region.end)),
+            (false, None) => return Ok(None),
+            (false, Some(w)) => {
+                if w.start < region.start {
+                    replacement.push(w.start..region.start);
+                }
+                if w.end > region.end {
+                    replacement.push(region.end..w.end);
+                }
+            }
+        }
+
+        let mut included = Vec::<TIncluded>::with_capacity(replacement.len());
+        let mut excluded = Vec::<TExcluded>::with_capacity(replacement.len());
+        let mut encoded_end = prev_end;
+        for (i, r) in replacement.iter().enumerate() {
+            excluded.push(if first == 0 && i == 0 {
+                checked_offset(r.start)?
+            } else {
+                checked_gap(encoded_end, r.start)?
+            });
+            included.push(checked_len(r.start, r.end)?);
+            encoded_end = r.end;
+        }
+        let next_gap = match next_start {
+            Some(start) if first == 0 && replacement.is_empty() => Some(checked_offset(start)?),
+            Some(start) => Some(checked_gap(encoded_end, start)?),
+            None => None,
+        };
+        Ok(Some(Splice {
+            entries: first..last,
+            included,
+            excluded,
+            ends: replacement.iter().map(|r| r.end).collect(),
+            next_gap,
+        }))
+    }
+
+    /// Replaces the entries of a planned splice and patches `index`, if given
+    pub(crate) fn apply_splice(
+        &mut self,
+        splice: Splice<TIncluded, TExcluded>,
+        index: Option<&mut EndIndex>,
+    ) {
+        let Splice {
+            entries,
+            included,
+            excluded,
+            ends,
+            next_gap,
+        } = splice;
+        let after = entries.start + included.len();
+        self.included.splice(entries.clone(), included);
+        self.excluded.splice(entries.clone(), excluded);
+        if let Some(gap) = next_gap {
+            self.excluded[after] = gap;
+        }
+        if let Some(EndIndex(index)) = index {
+            index.splice(entries, ends);
+        }
+    }
+}
+
+/// Edits a mask with an index of the absolute range ends. Each edit finds the affected ranges by
+/// binary search, so it costs O(log n) plus moving the entries after it, while creating the editor
+/// decodes the mask once. Errors leave the mask unchanged, like in `SortedRanges::insert_range`
+/// ```
+/// use std::{num::NonZero, ops::Range};
+/// use imask::{Rect, SortedRanges};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let size = NonZero::new(100).unwrap();
+/// let mut ranges = SortedRanges::<u8, u8>::empty(Rect::new(0, 0, size, size));
+/// let mut editor = ranges.editor()?;
+/// for y in 0..3 {
+///     editor.insert_range(y * 100 + 10..y * 100 + 20)?;
+/// }
+/// editor.remove_range(105..115)?;
+/// assert_eq!(
+///     vec![10u64..20, 115..120, 210..220],
+///     ranges.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub struct SortedRangesEditor<'a, TIncluded, TExcluded> {
+    ranges: &'a mut SortedRanges<TIncluded, TExcluded>,
+    index: EndIndex,
+}
+
+impl<TIncluded, TExcluded> SortedRangesEditor<'_, TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64>,
+{
+    pub fn insert_range(&mut self, range: Range<u64>) -> Result<(), BuildError> {
+        self.ranges.edit(range, true, Some(&mut self.index))
+    }
+
+    pub fn remove_range(&mut self, range: Range<u64>) -> Result<(), BuildError> {
+        self.ranges.edit(range, false, Some(&mut self.index))
+    }
+
+    /// Inserts all ranges. If an error occurs, the ranges before the failing one remain inserted
+    pub fn union_in_place(
+        &mut self,
+        ranges: impl IntoIterator<Item = Range<u64>>,
+    ) -> Result<(), BuildError> {
+        ranges.into_iter().try_for_each(|r| self.insert_range(r))
+    }
+
+    /// Removes all ranges. See `union_in_place`
+    pub fn subtract_in_place(
+        &mut self,
+        ranges: impl IntoIterator<Item = Range<u64>>,
+    ) -> Result<(), BuildError> {
+        ranges.into_iter().try_for_each(|r| self.remove_range(r))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::Rect;
+
+    use super::*;
+
+    fn sample() -> SortedRanges<u8, u8> {
+        let size = NonZero::new(100).unwrap();
+        SortedRanges::try_from_ordered_iter_roi(
+            [10u32..20, 30..40, 50..60],
+            Rect::new(0, 0, size, NonZero::<u32>::MIN),
+        )
+        .unwrap()
+    }
+
+    fn collect(ranges: &SortedRanges<u8, u8>) -> Vec<Range<u64>> {
+        ranges.iter_roi().collect()
+    }
+
+    #[test]
+    fn insert_merges_neighbours() {
+        let mut ranges = sample();
+        ranges.insert_range(20..30).unwrap();
+        assert_eq!(vec![10..40, 50..60], collect(&ranges));
+        ranges.insert_range(0..5).unwrap();
+        ranges.insert_range(70..80).unwrap();
+        ranges.insert_range(45..52).unwrap();
+        assert_eq!(vec![0..5, 10..40, 45..60, 70..80], collect(&ranges));
+        ranges.insert_range(0..100).unwrap();
+        assert_eq!(vec![0..100], collect(&ranges));
+    }
+
+    #[test]
+    fn remove_splits_and_drops_ranges() {
+        let mut ranges = sample();
+        ranges.remove_range(12..15).unwrap();
+        assert_eq!(vec![10..12, 15..20, 30..40, 50..60], collect(&ranges));
+        ranges.remove_range(5..35).unwrap();
+        assert_eq!(vec![35..40, 50..60], collect(&ranges));
+        ranges.remove_range(40..50).unwrap();
+        assert_eq!(vec![35..40, 50..60], collect(&ranges));
+        ranges.remove_range(0..100).unwrap();
+        assert!(ranges.is_empty());
+    }
+
+    #[test]
+    fn sorted_and_unsorted_batches() {
+        let mut ranges = sample();
+        ranges.union_in_place([0..2, 20..25, 58..65, 5..7]).unwrap();
+        assert_eq!(vec![0..2, 5..7, 10..25, 30..40, 50..65], collect(&ranges));
+        ranges.subtract_in_place([1..6, 22..31, 0..1]).unwrap();
+        assert_eq!(vec![6..7, 10..22, 31..40, 50..65], collect(&ranges));
+    }
+
+    #[test]
+    fn errors_keep_mask_unchanged() {
+        let mut ranges = sample();
+        assert_eq!(
+            Err(BuildError::OutOfBounds {
+                end: 101,
+                size: 100
+            }),
+            ranges.insert_range(90..101)
+        );
+        // Removing 200..210 joins the surrounding gaps to 430 > u8::MAX
+        let size = NonZero::new(1000).unwrap();
+        let mut wide = SortedRanges::<u8, u8>::try_from_ordered_iter_roi(
+            [10u32..20, 200..210, 450..460],
+            Rect::new(0, 0, size, size),
+        )
+        .unwrap();
+        let before = wide.clone();
+        assert_eq!(
+            Err(BuildError::GapOverflow { gap: 430 }),
+            wide.remove_range(200..210)
+        );
+        assert_eq!(before, wide);
+        // Merged ranges must fit into TIncluded as well
+        assert_eq!(
+            Err(BuildError::LengthOverflow { len: 450 }),
+            wide.insert_range(10..460)
+        );
+        assert_eq!(before, wide);
+        assert_eq!(vec![10..20, 30..40, 50..60], collect(&ranges));
+    }
+
+    #[test]
+    fn edits_near_the_end_of_a_large_mask() {
+        // 100k ranges of length 2 every 4 pixels
+        let (width, count) = (1000u32, 100_000u64);
+        let bounds = Rect::new(
+            0,
+            0,
+            NonZero::new(width).unwrap(),
+            NonZero::new(401).unwrap(),
+        );
+        let mut ranges = SortedRanges::<u8, u8>::try_from_ordered_iter_roi(
+            (0..count).map(|i| i * 4..i * 4 + 2),
+            bounds,
+        )
+        .unwrap();
+        let mut one_off = ranges.clone();
+        let end = count * 4;
+        let stroke = [end - 10..end - 5, end - 3..end + 2, end - 40..end - 38];
+        let mut editor = ranges.editor().unwrap();
+        editor.union_in_place(stroke.clone()).unwrap();
+        editor.remove_range(end - 20..end - 17).unwrap();
+        one_off.union_in_place(stroke).unwrap();
+        one_off.remove_range(end - 20..end - 17).unwrap();
+        assert_eq!(one_off, ranges);
+
+        let tail = ranges.iter_roi::<Range<u64>>().skip(count as usize - 10);
+        assert_eq!(
+            vec![
+                end - 40..end - 38,
+                end - 36..end - 34,
+                end - 32..end - 30,
+                end - 28..end - 26,
+                end - 24..end - 22,
+                end - 16..end - 14,
+                end - 12..end - 5,
+                end - 4..end + 2,
+            ],
+            tail.collect::<Vec<_>>()
+        );
+        assert_eq!(count - 2, ranges.iter_roi::<Range<u64>>().count() as u64);
+    }
+
+    #[test]
+    fn split_encoding_is_rejected() {
+        let size = NonZero::new(1000).unwrap();
+        let mut split = SortedRanges::<u8, u8>::try_from_ordered_iter_roi_split(
+            [10u32..20, 600..1500, 1800..1801],
+            Rect::new(0, 0, size, size),
+        )
+        .unwrap();
+        let before = split.clone();
+        assert_eq!(Err(BuildError::SplitEncoding), split.remove_range(700..710));
+        assert_eq!(Err(BuildError::SplitEncoding), split.insert_range(0..5));
+        assert_eq!(
+            Err(BuildError::SplitEncoding),
+            split.union_in_place([0..5, 30..40])
+        );
+        assert!(split.editor().is_err());
+        assert_eq!(before, split);
+
+        // A zero offset is no continuation
+        let mut ranges = SortedRanges::<u8, u8>::try_from_ordered_iter_roi(
+            [0u32..5, 10..20],
+            Rect::new(0, 0, size, size),
+        )
+        .unwrap();
+        ranges.remove_range(2..12).unwrap();
+        assert_eq!(vec![0..2, 12..20], collect(&ranges));
+    }
+}
@@ -48,37 +48,22 @@ where
         cx: &mut std::task::Context<'_>,
     ) -> std::task::Poll<Self::Output> {
         let mut this = self.project();
-        if this.builder.is_none() {
-            let size_hint = this.stream.size_hint().0;
-            match ready!(this.stream.as_mut().poll_next(cx)) {
-                Some(Ok(first_range)) => match Builder::new(first_range, size_hint) {
-                    Ok(x) => *this.builder = Some(x),
-                    Err(e) => return Ready(Err(e)),
-                },
-                Some(Err(e)) => return Ready(Err(e)),
-                None => {
-                    return Ready(Err(io::Error::new(
-                        io::ErrorKind::UnexpectedEof,
-                        "Requires at least one item",
-                    )));
-                }
-            }
-        };
+        let size_hint = this.stream.size_hint().0;
+        let builder = this
+            .builder
+            .get_or_insert_with(|| Builder::with_capacity(size_hint));
         loop {
             match ready!(this.stream.as_mut().poll_next(cx)) {
                 Some(Ok(x)) => {
-                    let builder = this
-                        .builder
-                        .as_mut()
-                        .expect("Created if non existend... Lifetime issue");
                     if let Err(e) = builder.add(x) {
-                        return Ready(Err(e));
+                        return Ready(Err(e.into()));
                     }
                 }
                 Some(Err(e)) => return Ready(Err(e)),
                 None => {
                     let width = this.stream.width();
-                    return Ready(this.builder.take().unwrap().build_global(width));
+                    let builder = this.builder.take().expect("Created above");
+                    return Ready(builder.build_global(width).map_err(Into::into));
                 }
             }
         }
@@ -112,4 +97,16 @@ mod tests {
         assert_eq!(stream_ranges, iter_ranges);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn empty_stream_creates_empty_mask() -> TestResult {
+        let ranges = SortedRanges::<u64, u64>::try_from_ordered_stream(WithBounds::new(
+            futures_util::stream::iter(std::iter::empty::<io::Result<std::ops::Range<u32>>>()),
+            NON_ZERO_1000,
+            NON_ZERO_1000,
+        ))
+        .await?;
+        assert!(ranges.is_empty());
+        Ok(())
+    }
 }
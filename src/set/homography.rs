@@ -0,0 +1,270 @@
+use std::{fmt::Display, iter::FusedIterator, num::NonZeroU32, ops::Range};
+
+use nalgebra::{Matrix3, Vector3};
+
+use super::affine_transform::{roi_local_matrix, row_edges};
+use crate::{BuildError, CreateRange, ImageDimension, ImaskSet, Rect, SortedRanges, UncheckedCast};
+
+/// Points closer to the horizon than this are treated as behind the camera
+const MIN_W: f64 = 1e-9;
+
+/// Projective transform of a mask with known bounds. See `ImaskSet::homography`
+pub struct HomographyIter {
+    ranges: std::vec::IntoIter<Range<u64>>,
+    bounds: Rect<u32>,
+}
+
+impl HomographyIter {
+    pub fn new<R, I>(ranges: I, matrix: &Matrix3<f64>, output_bounds: Rect<u32>) -> Self
+    where
+        R: CreateRange,
+        R::Item: Into<u64>,
+        I: Iterator<Item = R> + ImageDimension,
+    {
+        let input_bounds = ranges.bounds();
+        let matrix = roi_local_matrix(matrix, input_bounds, output_bounds);
+        let input_width = u64::from(input_bounds.width.get());
+        let (width, height) = (output_bounds.width.get(), output_bounds.height.get());
+
+        let mut spans = Vec::new();
+        for range in ranges {
+            let (start, end): (u64, u64) = (range.start().into(), range.end().into());
+            let mut pos = start;
+            while pos < end {
+                let row = pos / input_width;
+                let next_row = (row + 1) * input_width;
+                let col_start = pos - row * input_width;
+                let col_end = end.min(next_row) - row * input_width;
+                let polygon = project_segment(&matrix, col_start, col_end, row);
+                rasterize(&polygon, width, height, &mut spans);
+                pos = next_row;
+            }
+        }
+        Self {
+            ranges: merge_spans(spans, width).into_iter(),
+            bounds: output_bounds,
+        }
+    }
+}
+
+impl Iterator for HomographyIter {
+    type Item = Range<u64>;
+
+    fn next(&mut self) -> Option<Range<u64>> {
+        self.ranges.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ranges.size_hint()
+    }
+}
+
+impl FusedIterator for HomographyIter {}
+
+impl ImageDimension for HomographyIter {
+    fn bounds(&self) -> Rect<u32> {
+        self.bounds
+    }
+    fn width(&self) -> NonZeroU32 {
+        self.bounds.width
+    }
+}
+
+/// Outline of the pixels `col_start..col_end` in `row` after the projection. The outline of a
+/// rectangle remains a convex polygon, as long as it doesn't cross the horizon (w = 0).
+/// Parts behind the horizon are cut away in homogeneous coordinates before dividing by w.
+fn project_segment(
+    matrix: &Matrix3<f64>,
+    col_start: u64,
+    col_end: u64,
+    row: u64,
+) -> Vec<(f64, f64)> {
+    let (left, right) = (col_start as f64 - 0.5, col_end as f64 - 0.5);
+    let (top, bottom) = (row as f64 - 0.5, row as f64 + 0.5);
+    let corners = [(left, top), (right, top), (right, bottom), (left, bottom)]
+        .map(|(x, y)| matrix * Vector3::new(x, y, 1.0));
+
+    let mut clipped = Vec::with_capacity(5);
+    for (i, &a) in corners.iter().enumerate() {
+        let b = corners[(i + 1) % corners.len()];
+        if a.z >= MIN_W {
+            clipped.push(a);
+        }
+        if (a.z >= MIN_W) != (b.z >= MIN_W) {
+            let t = (MIN_W - a.z) / (b.z - a.z);
+            clipped.push(a + (b - a) * t);
+        }
+    }
+    clipped
+        .into_iter()
+        .map(|v| (v.x / v.z, v.y / v.z))
+        .collect()
+}
+
+/// Adds `(y, x_start, x_end)` for all pixels, whose center lies within `polygon` and the output
+fn rasterize(polygon: &[(f64, f64)], width: u32, height: u32, spans: &mut Vec<(u32, u32, u32)>) {
+    if polygon.len() < 3 {
+        return;
+    }
+    let min_y = polygon.iter().map(|c| c.1).fold(f64::MAX, f64::min);
+    let max_y = polygon.iter().map(|c| c.1).fold(f64::MIN, f64::max);
+    let py_start = min_y.ceil().max(0.0) as u32;
+    let py_end = max_y.floor().min(f64::from(height) - 1.0);
+    if py_end < 0.0 {
+        return;
+    }
+    for py in py_start..=py_end as u32 {
+        let Some((left, right)) = row_edges(polygon, f64::from(py)) else {
+            continue;
+        };
+        let px_start = left.ceil().max(0.0);
+        let px_end = right.floor().min(f64::from(width) - 1.0);
+        if px_start <= px_end {
+            spans.push((py, px_start as u32, px_end as u32 + 1));
+        }
+    }
+}
+
+/// Sorts and merges overlapping or touching spans into ranges of an area of `width`.
+/// Offsets are computed in u64, as `width * height` may exceed `u32::MAX`
+fn merge_spans(spans: Vec<(u32, u32, u32)>, width: u32) -> Vec<Range<u64>> {
+    let width = u64::from(width);
+    let mut ranges: Vec<Range<u64>> = spans
+        .into_iter()
+        .map(|(y, start, end)| {
+            let offset = u64::from(y) * width;
+            offset + u64::from(start)..offset + u64::from(end)
+        })
+        .collect();
+    ranges.sort_unstable_by_key(|r| r.start);
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+{
+    /// Like `transform`, but `matrix` is a homography. See `ImaskSet::homography`
+    /// ```
+    /// use std::{num::NonZero, ops::Range};
+    /// use imask::{Rect, SortedRanges};
+    /// use nalgebra::Matrix3;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let size = NonZero::new(4).unwrap();
+    /// let bounds = Rect::new(0, 0, size, size);
+    /// let ranges = SortedRanges::<u16, u16>::try_from_ordered_iter_roi([0u32..16], bounds)?;
+    /// // Shrinks the mask towards the origin, stronger with growing x
+    /// let matrix = Matrix3::new(
+    ///     1.0, 0.0, 0.0,
+    ///     0.0, 1.0, 0.0,
+    ///     0.5, 0.0, 1.0,
+    /// );
+    /// let projected = ranges.transform_perspective(&matrix, bounds)?;
+    /// assert_eq!(
+    ///     vec![0u64..2, 4..6, 8..9, 12..13],
+    ///     projected.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn transform_perspective(
+        &self,
+        matrix: &Matrix3<f64>,
+        output_bounds: Rect<u32>,
+    ) -> Result<Self, BuildError> {
+        let projected = self
+            .iter_roi::<Range<u64>>()
+            .with_roi(self.bounds)
+            .homography(matrix, output_bounds);
+        Self::try_from_ordered_iter_roi(projected, output_bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use super::*;
+
+    const SIZE: NonZeroU32 = NonZero::new(8).unwrap();
+    const BOUNDS: Rect<u32> = Rect::new(0, 0, SIZE, SIZE);
+
+    fn square() -> impl Iterator<Item = Range<u32>> + ImageDimension {
+        Rect::new(2u32, 2, NonZero::new(4).unwrap(), NonZero::new(4).unwrap())
+            .into_rect_iter::<Range<u32>>(SIZE)
+            .with_roi(BOUNDS)
+    }
+
+    #[test]
+    fn matches_sampling_with_inverse() {
+        let matrix = Matrix3::new_rotation(20.0_f64.to_radians());
+        let inverse = matrix.try_inverse().unwrap();
+        let expected: Vec<u64> = (0..64)
+            .filter(|i| {
+                let v = inverse * Vector3::new((i % 8) as f64, (i / 8) as f64, 1.0);
+                let (x, y) = ((v.x / v.z).round(), (v.y / v.z).round());
+                (2.0..6.0).contains(&x) && (2.0..6.0).contains(&y)
+            })
+            .collect();
+        let result = square()
+            .homography(&matrix, BOUNDS)
+            .flatten()
+            .collect::<Vec<_>>();
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn scaling_w_shrinks_shape() {
+        let matrix = Matrix3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 2.0);
+        let result = square().homography(&matrix, BOUNDS).collect::<Vec<_>>();
+        // Pixel centers 1..=2 are covered by 0.75..2.75
+        assert_eq!(vec![9..11, 17..19], result);
+    }
+
+    #[test]
+    fn trapezoid_is_not_a_parallelogram() {
+        // Rows further down are stretched more
+        let matrix = Matrix3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, -0.1, 1.0);
+        let size = NonZero::new(32).unwrap();
+        let output = Rect::new(0, 0, size, size);
+        let result = square().homography(&matrix, output).collect::<Vec<_>>();
+        let row_lengths: Vec<_> = result.iter().map(|r| r.end - r.start).collect();
+        assert!(row_lengths.first() < row_lengths.last(), "{result:?}");
+    }
+
+    #[test]
+    fn offsets_beyond_u32() {
+        let size = NonZero::new(100_000).unwrap();
+        let corner = Rect::new(99_990, 99_990, NonZero::new(2).unwrap(), SIZE);
+        let result = [0u32..2, 3..4]
+            .with_roi(corner)
+            .homography(&Matrix3::identity(), Rect::new(0, 0, size, size))
+            .collect::<Vec<_>>();
+        let start = 99_990 * 100_000 + 99_990;
+        assert_eq!(
+            vec![start..start + 2, start + 100_001..start + 100_002],
+            result
+        );
+    }
+
+    #[test]
+    fn geometry_behind_horizon_is_clipped() {
+        // w becomes negative for x > 4, the visible part stretches towards infinity
+        let matrix = Matrix3::new(1.0, 0.0, 0.0, 0.0, 1.0, 0.0, -0.25, 0.0, 1.0);
+        let result = square().homography(&matrix, BOUNDS).collect::<Vec<_>>();
+        assert_eq!(Some(63), result.last().map(|r| r.end - 1));
+        for range in result {
+            assert!(range.end <= 8 * 8, "{range:?}");
+            assert!(range.clone().all(|i| i % 8 >= 3), "{range:?}");
+        }
+    }
+}
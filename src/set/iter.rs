@@ -10,6 +10,8 @@ pub struct SortedRangesIter<TIncludedIter, TExcludedIter, TOut: CreateRange> {
     include: TIncludedIter,
     excluded: TExcludedIter,
     accumulator: TOut::Item,
+    /// Gap read ahead while looking for length continuations
+    pending_exclude: Option<TOut::Item>,
     width: NonZeroU32,
     height: NonZeroU32,
 }
@@ -28,6 +30,7 @@ impl<TIncludedIter, TExcludedIter, TRange: CreateRange>
             include,
             excluded,
             accumulator,
+            pending_exclude: None,
             width,
             height,
         }
@@ -60,15 +63,36 @@ where
     type Item = TOut;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let exclude = self.excluded.next()?.cast_unchecked();
-        self.accumulator = self.accumulator + exclude;
+        // Zero lengths continue the gap (see `SortedRanges::try_from_ordered_iter_roi_split`)
+        let (start, mut len) = loop {
+            let exclude = match self.pending_exclude.take() {
+                Some(exclude) => exclude,
+                None => self.excluded.next()?.cast_unchecked(),
+            };
+            self.accumulator = self.accumulator + exclude;
+            let include: TOut::Item = self.include.next()?.cast_unchecked();
+            if let Some(include) = include.create_non_zero() {
+                break (self.accumulator, include);
+            }
+        };
+        self.accumulator = self.accumulator.add_nonzero(len);
 
-        let include = self.include.next()?.cast_unchecked();
-        let out_range =
-            TOut::new_debug_checked(self.accumulator, include.create_non_zero().unwrap());
-        self.accumulator = self.accumulator + include;
+        // Zero gaps continue the length
+        for exclude in self.excluded.by_ref() {
+            let exclude: TOut::Item = exclude.cast_unchecked();
+            if exclude.create_non_zero().is_some() {
+                self.pending_exclude = Some(exclude);
+                break;
+            }
+            let Some(include) = self.include.next() else {
+                break;
+            };
+            let include: TOut::Item = include.cast_unchecked();
+            self.accumulator = self.accumulator + include;
+            len = (len.into() + include).create_non_zero().unwrap();
+        }
 
-        Some(out_range)
+        Some(TOut::new_debug_checked(start, len))
     }
 }
 
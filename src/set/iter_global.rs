@@ -7,19 +7,27 @@ use std::{
 
 use crate::{CreateRange, ImageDimension, Rect, SignedNonZeroable, UncheckedCast};
 
+/// Ranges of a mask placed into a parent image of `width` columns. The ROI offset of the mask is
+/// applied and columns beyond `width` are cut, like in `SortedRangesMapIterGlobal`. Ranges are split
+/// at row ends, unless the rows stay contiguous, i.e. the ROI starts at column 0 and is at least as
+/// wide as the parent image. Touching output ranges are merged
 pub struct SortedRangesIterGlobal<I, E, T: CreateRange> {
     included: I,
     excluded: E,
+    /// Position within the ROI of the part not yielded yet
     pos: T::Item,
     remaining: T::Item,
     old_width: T::Item,
     new_width_out: T::Item,
     new_width: NonZeroU32,
-    /// Buffered output range end for the shrinking path (`old_width >= new_width`).
-    /// When non-zero, a range starting at `pending_start` is being accumulated;
-    /// it is flushed when the next segment is not adjacent.
+    roi_x: T::Item,
+    roi_y: T::Item,
+    /// Buffered output range of the contiguous path. It is flushed when the next segment is not
+    /// adjacent.
     pending_start: T::Item,
     pending_end: T::Item,
+    /// Gap read ahead while looking for length continuations
+    pending_gap: Option<T::Item>,
     new_height: NonZeroU32,
 }
 
@@ -28,24 +36,21 @@ where
     u32: UncheckedCast<T::Item>,
     T::Item: Copy + Default,
 {
-    pub(crate) fn new(
-        included: I,
-        excluded: E,
-        old_width: NonZeroU32,
-        new_width: NonZeroU32,
-        new_height: NonZeroU32,
-    ) -> Self {
+    pub(crate) fn new(included: I, excluded: E, roi: Rect<u32>, new_width: NonZeroU32) -> Self {
         Self {
             included,
             excluded,
             pos: T::Item::default(),
             remaining: T::Item::default(),
-            old_width: old_width.get().cast_unchecked(),
+            old_width: roi.width.get().cast_unchecked(),
             new_width,
-            new_height,
+            new_height: NonZeroU32::new(roi.height.get() + roi.y).unwrap(),
             new_width_out: new_width.get().cast_unchecked(),
+            roi_x: roi.x.cast_unchecked(),
+            roi_y: roi.y.cast_unchecked(),
             pending_start: T::Item::default(),
             pending_end: T::Item::default(),
+            pending_gap: None,
         }
     }
 }
@@ -85,33 +90,56 @@ where
 
     fn next(&mut self) -> Option<Self::Item> {
         let zero = TOut::Item::default();
-        if self.remaining > zero {
-            let col = self.pos % self.old_width;
-            let row = self.pos / self.old_width;
-            let take = min(self.remaining, self.old_width - col);
-            self.pos = self.pos + take;
-            self.remaining = self.remaining - take;
-            let s = row * self.new_width_out + col;
-            return Some(TOut::new_debug_checked_zeroable(s, s + take));
-        }
+        let place = |p: TOut::Item, col: TOut::Item| {
+            (p / self.old_width + self.roi_y) * self.new_width_out
+                + min(col + self.roi_x, self.new_width_out)
+        };
 
-        while let Some(gap) = self.excluded.next() {
-            self.pos = self.pos + gap.cast_unchecked();
-            let include: TOut::Item = self.included.next()?.cast_unchecked();
+        loop {
+            if self.remaining > zero {
+                let col = self.pos % self.old_width;
+                let take = min(self.remaining, self.old_width - col);
+                let (s, e) = (place(self.pos, col), place(self.pos, col + take));
+                self.pos = self.pos + take;
+                self.remaining = self.remaining - take;
+                if s < e {
+                    return Some(TOut::new_debug_checked_zeroable(s, e));
+                }
+                continue;
+            }
+
+            let Some(gap) = self
+                .pending_gap
+                .take()
+                .or_else(|| self.excluded.next().map(UncheckedCast::cast_unchecked))
+            else {
+                break;
+            };
+            self.pos = self.pos + gap;
+            let mut include: TOut::Item = self.included.next()?.cast_unchecked();
+            // Zero gaps continue the length (see `SortedRanges::try_from_ordered_iter_roi_split`)
+            for next_gap in self.excluded.by_ref() {
+                let next_gap: TOut::Item = next_gap.cast_unchecked();
+                if next_gap > zero {
+                    self.pending_gap = Some(next_gap);
+                    break;
+                }
+                let Some(more) = self.included.next() else {
+                    break;
+                };
+                include = include + more.cast_unchecked();
+            }
 
-            if self.old_width < self.new_width_out {
-                // Expanding: pieces have gaps in output space, no merging needed
+            if self.roi_x > zero || self.old_width < self.new_width_out {
+                // Rows have gaps in output space: Split at row ends, no merging needed
                 self.remaining = include;
-                return self.next();
+                continue;
             }
 
-            // Shrinking: remap through row/col, clamping columns to new_width.
+            // Rows are contiguous: Remap both ends, clamping columns to new_width.
             // Merge adjacent output segments at row boundaries.
             let end = self.pos + include;
-            let remap = |p: TOut::Item| {
-                (p / self.old_width) * self.new_width_out
-                    + min(p % self.old_width, self.new_width_out)
-            };
+            let remap = |p: TOut::Item| place(p, p % self.old_width);
             let (s, e) = (remap(self.pos), remap(end));
             self.pos = end;
 
@@ -125,7 +153,7 @@ where
                 }
             }
         }
-        // Flush pending range from shrinking path
+        // Flush pending range of the contiguous path
         (self.pending_start < self.pending_end).then(|| {
             let r = TOut::new_debug_checked_zeroable(self.pending_start, self.pending_end);
             self.pending_start = self.pending_end;
@@ -179,3 +207,62 @@ mod range_set_blaze_0_5_interop {
     {
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZero, ops::Range};
+
+    use crate::{SortedRanges, SortedRangesMap};
+
+    use super::*;
+
+    /// ROI (2, 1, 4x3)
+    /// ```text
+    /// ####
+    /// ..##
+    /// ####
+    /// ```
+    const ROI: Rect<u32> = Rect::new(2, 1, NonZero::new(4).unwrap(), NonZero::new(3).unwrap());
+
+    #[test]
+    fn roi_offset_is_applied() {
+        let ranges =
+            SortedRanges::<u8, u8>::try_from_ordered_iter_roi([0u32..4, 6..12], ROI).unwrap();
+        let expected: [(u32, Vec<Range<u64>>); 4] = [
+            (3, vec![5..6, 11..12]),
+            (5, vec![7..10, 14..15, 17..20]),
+            (10, vec![12..16, 24..26, 32..36]),
+            (20, vec![22..26, 44..46, 62..66]),
+        ];
+        for (width, expected) in expected {
+            let width = NonZero::new(width).unwrap();
+            let iter = ranges.iter_global_with::<Range<u64>>(width);
+            assert_eq!(
+                Rect::new(0, 0, width, NonZero::new(4).unwrap()),
+                iter.bounds()
+            );
+            assert_eq!(expected, iter.collect::<Vec<_>>(), "{width}");
+        }
+    }
+
+    #[test]
+    fn matches_map_with_equal_meta() {
+        let ranges =
+            SortedRanges::<u8, u8>::try_from_ordered_iter_roi([0u32..4, 6..12], ROI).unwrap();
+        let map =
+            SortedRangesMap::<u8, u8, Vec<()>>::from_ordered_ranges([(0..4, ()), (6..12, ())], ROI)
+                .unwrap();
+        for width in [3, 5, 10, 20] {
+            let width = NonZero::new(width).unwrap();
+            assert_eq!(
+                map.iter_global_with::<Range<u64>>(width)
+                    .map(|m| m.range)
+                    .collect::<Vec<_>>(),
+                ranges
+                    .iter_global_with::<Range<u64>>(width)
+                    .collect::<Vec<_>>(),
+                "{width}"
+            );
+        }
+    }
+}
@@ -3,12 +3,13 @@ use std::{
     ops::RangeInclusive, rc::Rc,
 };
 
-use crate::{CreateRange, ImageDimension, RangeToOffsetsIter, SortedRanges, UncheckedCast};
+use crate::{ImageDimension, RangeToOffsetsIter, SortedRanges, UncheckedCast};
 
 impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
     /// Transform the ranges in-place using a closure.
     /// The closure receives a SourceIterator and returns an iterator of `RangeInclusive<u64>`.
-    /// Returns Some(SortedRanges) if non-empty, None if empty.
+    /// If the closure yields no ranges, the result is an empty mask with the same bounds.
+    /// The result doesn't split values, so gaps and lengths have to fit into their types.
     /// ```
     /// use std::ops::RangeInclusive;
     /// use imask::{Rect, SortedRanges, SourceIterator, ImaskSet};
@@ -23,7 +24,7 @@ impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
     ///         let (start, end) = x.into_inner();
     ///         (start+5)..=(end + 5)
     ///     })
-    /// }).expect("Is not empty");
+    /// });
     /// assert_eq!(
     ///     vec!(15u64..25, 35..50, 55..65),
     ///     ranges.iter_roi_owned::<std::ops::Range<u64>>().collect::<Vec<_>>()
@@ -31,7 +32,7 @@ impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn map_inplace<TIter, TFun>(self, f: TFun) -> Option<Self>
+    pub fn map_inplace<TIter, TFun>(self, f: TFun) -> Self
     where
         TIter: Iterator<Item = RangeInclusive<u64>>,
         TFun: FnOnce(SourceIterator<TIncluded, TExcluded>) -> TIter,
@@ -80,7 +81,7 @@ impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
                 }
             }
         }
-        let not_empty = {
+        {
             let mut x = cell.borrow_mut();
             let col = &mut x.0;
             while let Some(tuple) = cache.pop_front() {
@@ -89,12 +90,14 @@ impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded> {
 
             col.included.truncate(write_pos);
             col.excluded.truncate(write_pos);
-            !x.0.included.is_empty()
-        };
+        }
 
-        not_empty.then(move|| {
-            Rc::try_unwrap(cell).expect("You mustn't move the SourceIterator outside the lambda provided to map_inplace").into_inner().0
-        })
+        Rc::try_unwrap(cell)
+            .expect(
+                "You mustn't move the SourceIterator outside the lambda provided to map_inplace",
+            )
+            .into_inner()
+            .0
     }
 }
 
@@ -129,17 +132,26 @@ where
     fn next(&mut self) -> Option<Self::Item> {
         let mut x = self.cell.borrow_mut();
         let (col, read_pos) = &mut *x;
-        if *read_pos >= self.original_len {
-            return None;
-        }
-        let exclude = (*col.excluded.get(*read_pos)?).cast_unchecked();
-        self.offset += exclude;
-
-        let include = (*col.included.get(*read_pos)?).cast_unchecked();
-        let out_range =
-            RangeInclusive::new_debug_checked(self.offset, NonZero::new(include).unwrap());
+        // Zero lengths continue the gap, zero gaps continue the length
+        let include = loop {
+            if *read_pos >= self.original_len {
+                return None;
+            }
+            let exclude: u64 = col.excluded[*read_pos].cast_unchecked();
+            let include: u64 = col.included[*read_pos].cast_unchecked();
+            self.offset += exclude;
+            *read_pos += 1;
+            if include > 0 {
+                break include;
+            }
+        };
+        let start = self.offset;
         self.offset += include;
-        *read_pos += 1;
+        while *read_pos < self.original_len && col.excluded[*read_pos].cast_unchecked() == 0u64 {
+            self.offset += col.included[*read_pos].cast_unchecked();
+            *read_pos += 1;
+        }
+        let out_range = start..=self.offset - 1;
 
         Some(out_range)
     }
@@ -0,0 +1,220 @@
+use std::{fmt::Display, num::NonZeroU32, ops::Range};
+
+use crate::{
+    BuildError, SortedRanges, UncheckedCast,
+    orientation::{Orientation, join_row_spans, split_into_row_spans},
+};
+
+/// Exact flips and rotations. Pixels are moved, not resampled, so applying the inverse operation
+/// restores the original mask. `image_width`/`image_height` describe the parent image, in which the
+/// ROI is repositioned. Rotations are clockwise.
+///
+/// Ranges which become longer than `TIncluded` or gaps which become bigger than `TExcluded` are reported as error.
+/// ```
+/// use std::{num::NonZero, ops::Range};
+/// use imask::{ImageDimension, Rect, SortedRanges};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let image = const { NonZero::new(10u32).unwrap() };
+/// let roi = Rect::new(1, 2, NonZero::new(3).unwrap(), NonZero::new(2).unwrap());
+/// // ##.
+/// // #..
+/// let ranges = SortedRanges::<u16, u16>::try_from_ordered_iter_roi([0u32..2, 3..4], roi)?;
+/// let rotated = ranges.rotate90(image)?;
+/// // ##
+/// // .#
+/// // ..
+/// assert_eq!(Rect::new(6, 1, NonZero::new(2).unwrap(), NonZero::new(3).unwrap()), rotated.bounds());
+/// assert_eq!(vec![0u64..2, 3..4], rotated.iter_roi::<Range<u64>>().collect::<Vec<_>>());
+/// # Ok(())
+/// # }
+/// ```
+impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+{
+    /// Mirrors along the vertical axis
+    pub fn flip_horizontal(&self, image_width: NonZeroU32) -> Result<Self, BuildError> {
+        self.reorient(Orientation::FlipHorizontal { image_width })
+    }
+
+    /// Mirrors along the horizontal axis
+    pub fn flip_vertical(&self, image_height: NonZeroU32) -> Result<Self, BuildError> {
+        self.reorient(Orientation::FlipVertical { image_height })
+    }
+
+    /// Swaps x and y. The parent image becomes `height` x `width`
+    pub fn transpose(&self) -> Result<Self, BuildError> {
+        self.reorient(Orientation::Transpose)
+    }
+
+    pub fn rotate90(&self, image_height: NonZeroU32) -> Result<Self, BuildError> {
+        self.reorient(Orientation::Rotate90 { image_height })
+    }
+
+    pub fn rotate180(
+        &self,
+        image_width: NonZeroU32,
+        image_height: NonZeroU32,
+    ) -> Result<Self, BuildError> {
+        self.reorient(Orientation::Rotate180 {
+            image_width,
+            image_height,
+        })
+    }
+
+    pub fn rotate270(&self, image_width: NonZeroU32) -> Result<Self, BuildError> {
+        self.reorient(Orientation::Rotate270 { image_width })
+    }
+
+    fn reorient(&self, orientation: Orientation) -> Result<Self, BuildError> {
+        let bounds = orientation.apply_rect(self.bounds)?;
+        let spans = split_into_row_spans(
+            self.iter_roi::<Range<u64>>().map(|r| (r, ())),
+            self.bounds.width,
+        );
+        let spans = orientation.apply_spans(spans, self.bounds.width, self.bounds.height);
+        let ranges = join_row_spans(spans, bounds.width);
+        Self::try_from_ordered_iter_roi(ranges.into_iter().map(|(r, ())| r), bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::{ImageDimension, Rect};
+
+    use super::*;
+
+    const IMAGE_WIDTH: NonZeroU32 = NonZero::new(10).unwrap();
+    const IMAGE_HEIGHT: NonZeroU32 = NonZero::new(8).unwrap();
+
+    /// ROI (2, 1, 4x3) with the pattern
+    /// ```text
+    /// ###.
+    /// ...#
+    /// ##..
+    /// ```
+    fn sample() -> SortedRanges<u8, u8> {
+        let roi = Rect::new(2, 1, NonZero::new(4).unwrap(), NonZero::new(3).unwrap());
+        SortedRanges::try_from_ordered_iter_roi([0u32..3, 7..10], roi).unwrap()
+    }
+
+    fn collect(ranges: &SortedRanges<u8, u8>) -> Vec<Range<u64>> {
+        ranges.iter_roi().collect()
+    }
+
+    #[test]
+    fn flip_horizontal() {
+        let flipped = sample().flip_horizontal(IMAGE_WIDTH).unwrap();
+        assert_eq!(
+            Rect::new(4, 1, NonZero::new(4).unwrap(), NonZero::new(3).unwrap()),
+            flipped.bounds()
+        );
+        // .###
+        // #...
+        // ..##
+        assert_eq!(vec![1..5, 10..12], collect(&flipped));
+    }
+
+    #[test]
+    fn flip_vertical() {
+        let flipped = sample().flip_vertical(IMAGE_HEIGHT).unwrap();
+        assert_eq!(
+            Rect::new(2, 4, NonZero::new(4).unwrap(), NonZero::new(3).unwrap()),
+            flipped.bounds()
+        );
+        // ##..
+        // ...#
+        // ###.
+        assert_eq!(vec![0..2, 7..11], collect(&flipped));
+    }
+
+    #[test]
+    fn transpose() {
+        let transposed = sample().transpose().unwrap();
+        assert_eq!(
+            Rect::new(1, 2, NonZero::new(3).unwrap(), NonZero::new(4).unwrap()),
+            transposed.bounds()
+        );
+        // #.#
+        // #.#
+        // #..
+        // .#.
+        assert_eq!(vec![0..1, 2..4, 5..7, 10..11], collect(&transposed));
+    }
+
+    #[test]
+    fn rotations() {
+        let r90 = sample().rotate90(IMAGE_HEIGHT).unwrap();
+        assert_eq!(
+            Rect::new(4, 2, NonZero::new(3).unwrap(), NonZero::new(4).unwrap()),
+            r90.bounds()
+        );
+        // #.#
+        // #.#
+        // ..#
+        // .#.
+        assert_eq!(vec![0..1, 2..4, 5..6, 8..9, 10..11], collect(&r90));
+
+        let r180 = sample().rotate180(IMAGE_WIDTH, IMAGE_HEIGHT).unwrap();
+        assert_eq!(
+            Rect::new(4, 4, NonZero::new(4).unwrap(), NonZero::new(3).unwrap()),
+            r180.bounds()
+        );
+        // ..##
+        // #...
+        // .###
+        assert_eq!(vec![2..5, 9..12], collect(&r180));
+
+        let r270 = sample().rotate270(IMAGE_WIDTH).unwrap();
+        assert_eq!(
+            Rect::new(1, 4, NonZero::new(3).unwrap(), NonZero::new(4).unwrap()),
+            r270.bounds()
+        );
+        assert_eq!(r90.rotate180(IMAGE_HEIGHT, IMAGE_WIDTH).unwrap(), r270);
+    }
+
+    #[test]
+    fn four_rotations_restore_original() {
+        let original = sample();
+        let rotated = original
+            .rotate90(IMAGE_HEIGHT)
+            .and_then(|x| x.rotate90(IMAGE_WIDTH))
+            .and_then(|x| x.rotate90(IMAGE_HEIGHT))
+            .and_then(|x| x.rotate90(IMAGE_WIDTH))
+            .unwrap();
+        assert_eq!(original, rotated);
+    }
+
+    #[test]
+    fn roi_outside_of_image_causes_error() {
+        let error = sample()
+            .flip_horizontal(NonZero::new(5).unwrap())
+            .unwrap_err();
+        assert_eq!(
+            BuildError::RoiExceedsImageWidth {
+                roi: sample().bounds(),
+                image_width: NonZero::new(5).unwrap(),
+            },
+            error
+        );
+        // ROI (2, 1, 4x3) ends at row 4
+        assert_eq!(
+            Err(BuildError::RoiExceedsImageHeight {
+                roi: sample().bounds(),
+                image_height: NonZero::new(3).unwrap(),
+            }),
+            sample().flip_vertical(NonZero::new(3).unwrap())
+        );
+        assert_eq!(
+            "ROI Rect { x: 2, y: 1, width: 4, height: 3 } exceeds the image height of 3",
+            sample()
+                .rotate90(NonZero::new(3).unwrap())
+                .unwrap_err()
+                .to_string()
+        );
+    }
+}
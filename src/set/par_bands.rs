@@ -0,0 +1,221 @@
+use std::{fmt::Display, num::NonZeroU32, ops::Range};
+
+use rayon::prelude::*;
+
+use crate::{BuildError, ImaskSet, Rect, SortedRanges, UncheckedCast};
+
+/// Part of a mask, which is processed on its own thread. See `SortedRanges::par_row_bands`
+#[derive(Debug, Clone)]
+pub struct RowBand<TIncluded, TExcluded> {
+    /// Mask of the band including the halo rows. Its bounds are relative to the parent image
+    pub mask: SortedRanges<TIncluded, TExcluded>,
+    /// Rows of the ROI of the original mask, which belong to this band (without halo)
+    pub rows: Range<u32>,
+}
+
+impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display> + Send + Sync,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display> + Send + Sync,
+{
+    /// Splits the mask into bands of `band_height` rows. Each band additionally contains up to
+    /// `halo` rows above and below, so operations with a vertical reach (e.g. dilate) see the pixels
+    /// of their neighbours. The mask is streamed once with `split_rows`, each row is copied into the
+    /// bands containing it, and the band masks are encoded in parallel
+    pub fn split_row_bands(
+        &self,
+        band_height: NonZeroU32,
+        halo: u32,
+    ) -> Result<Vec<RowBand<TIncluded, TExcluded>>, BuildError> {
+        let (width, height) = (self.bounds.width, self.bounds.height.get());
+        let row_size = u64::from(width.get());
+        let band_rows =
+            |band: u32| band * band_height.get()..height.min((band + 1) * band_height.get());
+        let band_count = height.div_ceil(band_height.get());
+        let mut band_ranges = vec![Vec::<Range<u64>>::new(); band_count as usize];
+        for piece in self.iter_roi::<Range<u64>>().split_rows() {
+            let y = (piece.start / row_size) as u32;
+            let first = y.saturating_sub(halo) / band_height.get();
+            let last = y.saturating_add(halo).min(height - 1) / band_height.get();
+            for band in first..=last {
+                let offset = u64::from(band_rows(band).start.saturating_sub(halo)) * row_size;
+                let local = piece.start - offset..piece.end - offset;
+                let ranges = &mut band_ranges[band as usize];
+                // Rejoin ranges split at the row end
+                match ranges.last_mut() {
+                    Some(last) if last.end == local.start => last.end = local.end,
+                    _ => ranges.push(local),
+                }
+            }
+        }
+        band_ranges
+            .into_par_iter()
+            .zip(0..band_count)
+            .map(|(ranges, band)| {
+                let rows = band_rows(band);
+                let top = rows.start.saturating_sub(halo);
+                let bottom = height.min(rows.end.saturating_add(halo));
+                let bounds = Rect::new(
+                    self.bounds.x,
+                    self.bounds.y + top,
+                    width,
+                    NonZeroU32::new(bottom - top).expect("Bands are not empty"),
+                );
+                Ok(RowBand {
+                    mask: SortedRanges::try_from_ordered_iter_roi(ranges, bounds)?,
+                    rows,
+                })
+            })
+            .collect()
+    }
+
+    /// Runs `op` for every band in parallel and returns the results in band order,
+    /// e.g. to calculate statistics
+    pub fn par_row_bands<F, R>(
+        &self,
+        band_height: NonZeroU32,
+        halo: u32,
+        op: F,
+    ) -> Result<Vec<R>, BuildError>
+    where
+        F: Fn(&RowBand<TIncluded, TExcluded>) -> R + Sync,
+        R: Send,
+    {
+        Ok(self
+            .split_row_bands(band_height, halo)?
+            .par_iter()
+            .map(&op)
+            .collect())
+    }
+
+    /// Runs `op` for every band in parallel and stitches the results into a mask with the same ROI.
+    /// `op` returns sorted ranges relative to the band mask. Results within the halo rows are dropped,
+    /// so `halo` must cover the vertical reach of `op`
+    /// ```
+    /// use std::{num::NonZero, ops::Range};
+    /// use imask::{Rect, SortedRanges};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let size = NonZero::new(10).unwrap();
+    /// let mask = SortedRanges::<u16, u16>::try_from_ordered_iter_roi([3u32..4, 10..15, 44..46, 80..81], Rect::new(0, 0, size, size))?;
+    /// // Remove single pixel runs
+    /// let filtered = mask.par_map_row_bands(NonZero::new(4).unwrap(), 0, |band| {
+    ///     band.mask.iter_roi::<Range<u64>>().filter(|r| r.end - r.start > 1).collect::<Vec<_>>()
+    /// })?;
+    /// assert_eq!(vec![10u64..15, 44..46], filtered.iter_roi::<Range<u64>>().collect::<Vec<_>>());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn par_map_row_bands<F, I>(
+        &self,
+        band_height: NonZeroU32,
+        halo: u32,
+        op: F,
+    ) -> Result<Self, BuildError>
+    where
+        F: Fn(&RowBand<TIncluded, TExcluded>) -> I + Sync,
+        I: IntoIterator<Item = Range<u64>>,
+    {
+        let row_size = u64::from(self.bounds.width.get());
+        let bands = self.split_row_bands(band_height, halo)?;
+        let results = bands
+            .par_iter()
+            .map(|band| {
+                let top = band.mask.bounds.y - self.bounds.y;
+                let core = u64::from(band.rows.start - top) * row_size
+                    ..u64::from(band.rows.end - top) * row_size;
+                let offset = u64::from(top) * row_size;
+                op(band)
+                    .into_iter()
+                    .filter(|r| r.start < core.end && r.end > core.start)
+                    .map(|r| r.start.max(core.start) + offset..r.end.min(core.end) + offset)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(results.iter().map(Vec::len).sum());
+        for range in results.into_iter().flatten() {
+            match merged.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => merged.push(range),
+            }
+        }
+        Self::try_from_ordered_iter_roi(merged, self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::ImageDimension;
+
+    use super::*;
+
+    const SIZE: NonZeroU32 = NonZero::new(20).unwrap();
+
+    fn mask() -> SortedRanges<u16, u16> {
+        let ranges = [3u32..5, 23..25, 110..150, 205..206, 290..320, 399..400];
+        SortedRanges::try_from_ordered_iter_roi(ranges, Rect::new(3, 2, SIZE, SIZE)).unwrap()
+    }
+
+    #[test]
+    fn bands_with_halo() {
+        let bands = mask().split_row_bands(NonZero::new(8).unwrap(), 2).unwrap();
+        assert_eq!(
+            vec![0..8, 8..16, 16..20],
+            bands.iter().map(|b| b.rows.clone()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Rect::new(3, 8, SIZE, NonZero::new(12).unwrap()),
+            bands[1].mask.bounds()
+        );
+        // 110..150 ends within the halo of the second band (rows 6..18)
+        assert_eq!(
+            vec![0u64..30, 85..86, 170..200],
+            bands[1].mask.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn statistics_per_band() {
+        let counts = mask()
+            .par_row_bands(NonZero::new(8).unwrap(), 0, |band| {
+                band.mask
+                    .iter_roi::<Range<u64>>()
+                    .map(|r| r.end - r.start)
+                    .sum::<u64>()
+            })
+            .unwrap();
+        assert_eq!(vec![44, 31, 1], counts);
+    }
+
+    #[test]
+    #[cfg(feature = "range-set-blaze-0_5")]
+    fn parallel_dilate_matches_sequential() {
+        let mask = mask();
+        let offset = NonZero::new(2).unwrap();
+        let sequential = mask
+            .iter_roi::<Range<u64>>()
+            .with_roi(mask.bounds())
+            .dilate(offset)
+            // Sequential dilate grows beyond the bottom of the ROI
+            .filter(|r| r.start < 400)
+            .collect::<Vec<_>>();
+        for band_height in [1, 3, 8, 20] {
+            let parallel = mask
+                .par_map_row_bands(NonZero::new(band_height).unwrap(), 2, |band| {
+                    band.mask
+                        .iter_roi::<Range<u64>>()
+                        .with_roi(band.mask.bounds())
+                        .dilate(offset)
+                        .collect::<Vec<_>>()
+                })
+                .unwrap();
+            assert_eq!(
+                sequential,
+                parallel.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+            );
+        }
+    }
+}
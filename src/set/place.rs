@@ -0,0 +1,370 @@
+use std::{fmt::Display, iter::FusedIterator, num::NonZero, ops::Range};
+
+use crate::{
+    BuildError, CreateRange, ImageDimension, ImaskSet, Rect, SortedRanges, UncheckedCast,
+    orientation::{RowSpan, join_row_spans, split_into_row_spans},
+};
+
+/// Fractional offsets are rounded to multiples of `1 / SCALE` pixels, so coverage is exact
+const SCALE: i64 = 1 << 16;
+
+/// Translates a mask by signed offsets into another ROI. See `ImaskSet::place`
+#[derive(Debug, Clone)]
+pub struct PlaceIter<T> {
+    parent: T,
+    source: Rect<u32>,
+    target: Rect<u32>,
+    dx: i64,
+    dy: i64,
+    current: Option<Range<u64>>,
+    pending: Option<Range<u64>>,
+    done: bool,
+}
+
+impl<T: ImageDimension> PlaceIter<T> {
+    pub fn new(parent: T, target: Rect<u32>, dx: i64, dy: i64) -> Self {
+        Self {
+            source: parent.bounds(),
+            parent,
+            target,
+            dx,
+            dy,
+            current: None,
+            pending: None,
+            done: false,
+        }
+    }
+}
+
+impl<T> PlaceIter<T>
+where
+    T: Iterator<Item: CreateRange<Item: Into<u64>>>,
+{
+    /// Next part of a source range within a single row, which is moved into a target row
+    fn next_segment(&mut self) -> Option<(i64, Range<i64>)> {
+        let width = u64::from(self.source.width.get());
+        // First source row, which lands within the target
+        let first_row = i64::from(self.target.y) - i64::from(self.source.y) - self.dy;
+        loop {
+            let range = match self.current.take() {
+                Some(r) => r,
+                None => {
+                    let r = self.parent.next()?;
+                    r.start().into()..r.end().into()
+                }
+            };
+            let row = range.start / width;
+            if (row as i64) < first_row {
+                let skip_to = (first_row as u64 * width).min(range.end);
+                if skip_to < range.end {
+                    self.current = Some(skip_to..range.end);
+                }
+                continue;
+            }
+            let row_end = range.end.min((row + 1) * width);
+            if row_end < range.end {
+                self.current = Some(row_end..range.end);
+            }
+            let x = i64::from(self.source.x) + self.dx - (row * width) as i64;
+            let y = i64::from(self.source.y) + self.dy + row as i64;
+            return Some((y, range.start as i64 + x..row_end as i64 + x));
+        }
+    }
+}
+
+impl<T> Iterator for PlaceIter<T>
+where
+    T: Iterator<Item: CreateRange<Item: Into<u64>>>,
+{
+    type Item = Range<u64>;
+
+    fn next(&mut self) -> Option<Range<u64>> {
+        let (left, top) = (i64::from(self.target.x), i64::from(self.target.y));
+        let right = left + i64::from(self.target.width.get());
+        let bottom = top + i64::from(self.target.height.get());
+        let target_width = u64::from(self.target.width.get());
+
+        while !self.done {
+            let Some((y, columns)) = self.next_segment() else {
+                self.done = true;
+                break;
+            };
+            if y >= bottom {
+                self.done = true;
+                break;
+            }
+            let (start, end) = (columns.start.max(left), columns.end.min(right));
+            if start >= end {
+                continue;
+            }
+            let offset = (y - top) as u64 * target_width;
+            let range = offset + (start - left) as u64..offset + (end - left) as u64;
+            match self.pending.as_mut() {
+                Some(pending) if pending.end == range.start => pending.end = range.end,
+                _ => {
+                    if let Some(pending) = self.pending.replace(range) {
+                        return Some(pending);
+                    }
+                }
+            }
+        }
+        self.pending.take()
+    }
+}
+
+impl<T> FusedIterator for PlaceIter<T> where PlaceIter<T>: Iterator {}
+
+impl<T> ImageDimension for PlaceIter<T> {
+    fn bounds(&self) -> Rect<u32> {
+        self.target
+    }
+    fn width(&self) -> NonZero<u32> {
+        self.target.width
+    }
+}
+
+impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+{
+    /// Moves the mask by `dx`/`dy` and clips it to `target`. See `ImaskSet::place`
+    /// ```
+    /// use std::{num::NonZero, ops::Range};
+    /// use imask::{Rect, SortedRanges};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let size = NonZero::new(2).unwrap();
+    /// // Mask of a 2x2 crop at (5, 5)
+    /// let crop = SortedRanges::<u16, u16>::try_from_ordered_iter_roi([0u32..3], Rect::new(5, 5, size, size))?;
+    /// // Paste it into a 4x4 image at (1, 1)
+    /// let image = NonZero::new(4).unwrap();
+    /// let placed = crop.place(Rect::new(0, 0, image, image), -4, -4)?;
+    /// assert_eq!(vec![5u64..7, 9..10], placed.iter_roi::<Range<u64>>().collect::<Vec<_>>());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn place(&self, target: Rect<u32>, dx: i64, dy: i64) -> Result<Self, BuildError> {
+        let placed = self
+            .iter_roi::<Range<u64>>()
+            .with_roi(self.bounds)
+            .place(target, dx, dy);
+        Self::try_from_ordered_iter_roi(placed, target)
+    }
+
+    /// Like `place`, but `dx`/`dy` may be fractional. A pixel of `target` is set, if the fraction
+    /// of its area covered by the moved mask exceeds `threshold` (`0.0..1.0`), like in
+    /// `resize_coverage`. Offsets are rounded to 1/65536 pixel. Integer offsets give the result of
+    /// `place` for any threshold below 1
+    /// ```
+    /// use std::{num::NonZero, ops::Range};
+    /// use imask::{Rect, SortedRanges};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let size = NonZero::new(4).unwrap();
+    /// let bounds = Rect::new(0, 0, size, size);
+    /// let ranges = SortedRanges::<u16, u16>::try_from_ordered_iter_roi([0u32..2], bounds)?;
+    /// // Covers half of column 0, column 1 and half of column 2
+    /// let any = ranges.place_subpixel(bounds, 0.5, 0.0, 0.0)?;
+    /// assert_eq!(vec![0u64..3], any.iter_roi::<Range<u64>>().collect::<Vec<_>>());
+    /// let majority = ranges.place_subpixel(bounds, 0.5, 0.0, 0.5)?;
+    /// assert_eq!(vec![1u64..2], majority.iter_roi::<Range<u64>>().collect::<Vec<_>>());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn place_subpixel(
+        &self,
+        target: Rect<u32>,
+        dx: f64,
+        dy: f64,
+        threshold: f64,
+    ) -> Result<Self, BuildError> {
+        let (ix, fx) = split_offset(dx);
+        let (iy, fy) = split_offset(dy);
+        // Whole pixels between the origin of the source ROI and the origin of the target
+        let ox = i64::from(self.bounds.x) - i64::from(target.x) + ix;
+        let oy = i64::from(self.bounds.y) - i64::from(target.y) + iy;
+        let (target_w, target_h) = (
+            i64::from(target.width.get()),
+            i64::from(target.height.get()),
+        );
+
+        // A source row covers two target rows with the weights `SCALE - fy` and `fy`
+        let mut rows: Vec<(i64, i64, i64, i64)> = Vec::new();
+        let spans = split_into_row_spans(
+            self.iter_roi::<Range<u64>>().map(|r| (r, ())),
+            self.bounds.width,
+        );
+        for span in spans {
+            let y = i64::from(span.y) + oy;
+            let (start, end) = (i64::from(span.start) + ox, i64::from(span.end) + ox);
+            for (y, weight) in [(y, SCALE - fy), (y + 1, fy)] {
+                if weight > 0 && (0..target_h).contains(&y) {
+                    rows.push((y, start, end, weight));
+                }
+            }
+        }
+        rows.sort_by_key(|(y, ..)| *y);
+
+        let min_area = threshold * (SCALE * SCALE) as f64;
+        let mut events = Vec::new();
+        let mut spans = Vec::new();
+        for row in rows.chunk_by(|a, b| a.0 == b.0) {
+            events.clear();
+            for &(_, start, end, weight) in row {
+                add_shifted_events(&mut events, start, end, fx, weight);
+            }
+            events.sort_unstable_by_key(|(x, _)| *x);
+            let y = row[0].0 as u32;
+            let (mut level, mut open) = (0, None);
+            for columns in events.chunk_by(|a, b| a.0 == b.0) {
+                let column = columns[0].0;
+                level += columns.iter().map(|(_, delta)| delta).sum::<i64>();
+                match (open, level as f64 > min_area) {
+                    (None, true) => open = Some(column),
+                    (Some(start), false) => {
+                        let (start, end) = (start.max(0), column.min(target_w));
+                        if start < end {
+                            spans.push(RowSpan {
+                                y,
+                                start: start as u32,
+                                end: end as u32,
+                                meta: (),
+                            });
+                        }
+                        open = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let ranges = join_row_spans(spans, target.width);
+        Self::try_from_ordered_iter_roi(ranges.into_iter().map(|(r, ())| r), target)
+    }
+}
+
+/// Splits an offset into whole pixels and a fraction in units of `1 / SCALE`
+fn split_offset(offset: f64) -> (i64, i64) {
+    let whole = offset.floor();
+    let fraction = ((offset - whole) * SCALE as f64).round() as i64;
+    if fraction == SCALE {
+        (whole as i64 + 1, 0)
+    } else {
+        (whole as i64, fraction)
+    }
+}
+
+/// Adds the coverage of the columns `start..end` moved right by `fx / SCALE` as `(column, delta)`
+/// events. The first column is covered by `SCALE - fx`, the one after the end by `fx`
+fn add_shifted_events(events: &mut Vec<(i64, i64)>, start: i64, end: i64, fx: i64, weight: i64) {
+    events.push((start, (SCALE - fx) * weight));
+    events.push((start + 1, fx * weight));
+    events.push((end, -(SCALE - fx) * weight));
+    events.push((end + 1, -fx * weight));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use super::*;
+
+    const SIZE: NonZeroU32 = NonZero::new(4).unwrap();
+
+    /// ROI (2, 2, 4x4) of the parent image
+    /// ```text
+    /// ####
+    /// #..#
+    /// #..#
+    /// ####
+    /// ```
+    fn frame() -> impl Iterator<Item = Range<u32>> + ImageDimension {
+        [0u32..5, 7..9, 11..16]
+            .into_iter()
+            .with_roi(Rect::new(2, 2, SIZE, SIZE))
+    }
+
+    #[test]
+    fn same_roi_without_offset_is_identity() {
+        let placed: Vec<_> = frame().place(Rect::new(2, 2, SIZE, SIZE), 0, 0).collect();
+        assert_eq!(vec![0u64..5, 7..9, 11..16], placed);
+    }
+
+    #[test]
+    fn clip_on_every_side() {
+        let two = NonZero::new(2).unwrap();
+        // Target covers the inner 2x2 area
+        let placed: Vec<_> = frame().place(Rect::new(3, 3, two, two), 0, 0).collect();
+        assert!(placed.is_empty());
+        // Moving by (1, 1) shifts the top left corner of the frame into it
+        let placed: Vec<_> = frame().place(Rect::new(3, 3, two, two), 1, 1).collect();
+        // Touching parts of different rows are merged
+        assert_eq!(vec![0u64..3], placed);
+    }
+
+    #[test]
+    fn negative_coordinates_are_clipped() {
+        let placed: Vec<_> = frame().place(Rect::new(0, 0, SIZE, SIZE), -3, -3).collect();
+        // Only the bottom right 3x3 corner of the frame remains
+        assert_eq!(vec![2u64..3, 6..7, 8..11], placed);
+    }
+
+    #[test]
+    fn target_larger_than_source() {
+        let eight = NonZero::new(8).unwrap();
+        let placed: Vec<_> = frame().place(Rect::new(0, 0, eight, eight), 1, 0).collect();
+        assert_eq!(
+            vec![19u64..23, 27..28, 30..31, 35..36, 38..39, 43..47],
+            placed
+        );
+    }
+
+    #[test]
+    fn subpixel_with_integer_offsets_matches_place() {
+        let eight = NonZero::new(8).unwrap();
+        let target = Rect::new(0, 0, eight, eight);
+        let frame = SortedRanges::<u16, u16>::try_from_ordered_iter_roi(
+            frame().map(|r| r.start..r.end),
+            Rect::new(2, 2, SIZE, SIZE),
+        )
+        .unwrap();
+        for (dx, dy) in [(0, 0), (1, 0), (-3, 2)] {
+            assert_eq!(
+                frame.place(target, dx, dy).unwrap(),
+                frame
+                    .place_subpixel(target, dx as f64, dy as f64, 0.0)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn subpixel_coverage_threshold() {
+        let eight = NonZero::new(8).unwrap();
+        let target = Rect::new(0, 0, eight, eight);
+        let square = SortedRanges::<u16, u16>::try_from_ordered_iter_roi(
+            std::iter::once(0u32..16),
+            Rect::new(2, 2, SIZE, SIZE),
+        )
+        .unwrap();
+        // Covers 1.5..5.5 in both directions: Edges are covered by 0.5, corners by 0.25
+        let moved = square.place_subpixel(target, -0.5, -0.5, 0.2).unwrap();
+        assert_eq!(
+            vec![9u64..14, 17..22, 25..30, 33..38, 41..46],
+            moved.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+        );
+        let moved = square.place_subpixel(target, -0.5, -0.5, 0.3).unwrap();
+        assert_eq!(
+            vec![10u64..13, 17..22, 25..30, 33..38, 42..45],
+            moved.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+        );
+        // The bottom right corner, clipped by the target
+        let moved = square
+            .place_subpixel(Rect::new(3, 3, SIZE, SIZE), -0.5, -0.5, 0.3)
+            .unwrap();
+        assert_eq!(
+            vec![0u64..3, 4..7, 8..10],
+            moved.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+        );
+    }
+}
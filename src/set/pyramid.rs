@@ -0,0 +1,236 @@
+use std::{fmt::Display, num::NonZero, ops::Range};
+
+use crate::{
+    BuildError, ImaskSet, PlaceIter, Rect, SortedRanges, SortedRangesIter, UncheckedCast, WithRoi,
+};
+
+/// Decides, whether a pixel of the next level is set, based on its 2x2 source pixels.
+/// Source pixels outside of the image count as unset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownsampleRule {
+    /// At least one source pixel is set
+    Any,
+    /// All four source pixels are set
+    All,
+    /// At least three source pixels are set
+    Majority,
+}
+
+impl DownsampleRule {
+    /// Coverage threshold for `SortedRanges::resize_coverage`. Coverage is a multiple of 0.25
+    fn threshold(self) -> f64 {
+        match self {
+            DownsampleRule::Any => 0.0,
+            DownsampleRule::All => 0.75,
+            DownsampleRule::Majority => 0.5,
+        }
+    }
+}
+
+/// Series of masks, each downsampled by a factor of 2 from the previous level.
+/// Level 0 is the original mask. The ROI of each level is aligned to the pixel grid of its level,
+/// so pixel `(x, y)` of level `n` covers pixels `(x * 2^n, y * 2^n)` to `((x + 1) * 2^n, (y + 1) * 2^n)`
+/// of the original image
+/// ```
+/// use std::{num::NonZero, ops::Range};
+/// use imask::{DownsampleRule, ImageDimension, MaskPyramid, Rect, SortedRanges};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let size = NonZero::new(4).unwrap();
+/// // ###.
+/// // ##..
+/// // ....
+/// // ...#
+/// let mask = SortedRanges::<u16, u16>::try_from_ordered_iter_roi([0u32..3, 4..6, 15..16], Rect::new(0, 0, size, size))?;
+/// let pyramid = MaskPyramid::try_new(mask, DownsampleRule::Majority, 2)?;
+/// assert_eq!(3, pyramid.levels().len());
+/// let half = pyramid.level(1).unwrap();
+/// assert_eq!(Rect::new(0, 0, NonZero::new(2).unwrap(), NonZero::new(2).unwrap()), half.bounds());
+/// assert_eq!(vec![0u64..1], half.iter_roi::<Range<u64>>().collect::<Vec<_>>());
+///
+/// let viewport = Rect::new(0, 0, NonZero::new(3).unwrap(), NonZero::<u32>::MIN);
+/// assert_eq!(vec![0u64..1], pyramid.query(1, viewport).unwrap().collect::<Vec<_>>());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaskPyramid<TIncluded, TExcluded> {
+    levels: Vec<SortedRanges<TIncluded, TExcluded>>,
+    rule: DownsampleRule,
+}
+
+type QueryIter<'a, TIncluded, TExcluded> = PlaceIter<
+    WithRoi<
+        SortedRangesIter<
+            std::iter::Copied<std::slice::Iter<'a, TIncluded>>,
+            std::iter::Copied<std::slice::Iter<'a, TExcluded>>,
+            Range<u64>,
+        >,
+    >,
+>;
+
+impl<TIncluded, TExcluded> MaskPyramid<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+{
+    /// Builds up to `levels` downsampled levels. Stops early, once a level consists of a single pixel
+    pub fn try_new(
+        base: SortedRanges<TIncluded, TExcluded>,
+        rule: DownsampleRule,
+        levels: usize,
+    ) -> Result<Self, BuildError> {
+        let mut result = Vec::with_capacity(levels + 1);
+        result.push(base);
+        for _ in 0..levels {
+            let previous = result.last().expect("Contains the base level");
+            let bounds = previous.bounds;
+            if bounds.width.get() == 1 && bounds.height.get() == 1 {
+                break;
+            }
+            let next = downsample(previous, rule)?;
+            result.push(next);
+        }
+        Ok(Self {
+            levels: result,
+            rule,
+        })
+    }
+}
+
+impl<TIncluded, TExcluded> MaskPyramid<TIncluded, TExcluded> {
+    pub fn rule(&self) -> DownsampleRule {
+        self.rule
+    }
+
+    /// All levels, starting with the original mask
+    pub fn levels(&self) -> &[SortedRanges<TIncluded, TExcluded>] {
+        &self.levels
+    }
+
+    pub fn level(&self, level: usize) -> Option<&SortedRanges<TIncluded, TExcluded>> {
+        self.levels.get(level)
+    }
+
+    pub fn into_levels(self) -> Vec<SortedRanges<TIncluded, TExcluded>> {
+        self.levels
+    }
+
+    /// Ranges of `level` relative to `viewport`, which is given in the coordinates of this level.
+    /// The viewport may exceed the mask on every side. Returns None, if the level doesn't exist
+    pub fn query(
+        &self,
+        level: usize,
+        viewport: Rect<u32>,
+    ) -> Option<QueryIter<'_, TIncluded, TExcluded>>
+    where
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+    {
+        let mask = self.levels.get(level)?;
+        Some(
+            mask.iter_roi::<Range<u64>>()
+                .with_roi(mask.bounds)
+                .place(viewport, 0, 0),
+        )
+    }
+}
+
+/// Halves the mask. The ROI is first extended to even coordinates, so the scaled ROI is exact
+fn downsample<TIncluded, TExcluded>(
+    mask: &SortedRanges<TIncluded, TExcluded>,
+    rule: DownsampleRule,
+) -> Result<SortedRanges<TIncluded, TExcluded>, BuildError>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+{
+    let bounds = mask.bounds;
+    let (x, y) = (bounds.x & !1, bounds.y & !1);
+    let (x_end, y_end) = (
+        bounds.len_x().get().next_multiple_of(2),
+        bounds.len_y().get().next_multiple_of(2),
+    );
+    let aligned = Rect::new(
+        x,
+        y,
+        NonZero::new(x_end - x).expect("Not empty"),
+        NonZero::new(y_end - y).expect("Not empty"),
+    );
+    let padded;
+    let source = if aligned == bounds {
+        mask
+    } else {
+        padded = mask.place(aligned, 0, 0)?;
+        &padded
+    };
+    source.resize_coverage(
+        NonZero::new(aligned.width.get() / 2).expect("Width is at least 2"),
+        NonZero::new(aligned.height.get() / 2).expect("Height is at least 2"),
+        rule.threshold(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ImageDimension;
+
+    use super::*;
+
+    /// ROI (1, 1, 5x3) of the parent image
+    /// ```text
+    /// #####
+    /// .####
+    /// #.###
+    /// ```
+    fn mask() -> SortedRanges<u8, u8> {
+        let roi = Rect::new(1, 1, NonZero::new(5).unwrap(), NonZero::new(3).unwrap());
+        SortedRanges::try_from_ordered_iter_roi([0u32..5, 6..11, 12..15], roi).unwrap()
+    }
+
+    fn level_ranges(pyramid: &MaskPyramid<u8, u8>, level: usize) -> Vec<Range<u64>> {
+        pyramid.levels()[level].iter_roi().collect()
+    }
+
+    #[test]
+    fn levels_are_aligned_to_the_grid() {
+        let pyramid = MaskPyramid::try_new(mask(), DownsampleRule::Any, 10).unwrap();
+        let bounds = pyramid
+            .levels()
+            .iter()
+            .map(|l| l.bounds())
+            .collect::<Vec<_>>();
+        let rect = |x, y, w, h| Rect::new(x, y, NonZero::new(w).unwrap(), NonZero::new(h).unwrap());
+        assert_eq!(
+            vec![
+                rect(1, 1, 5, 3),
+                rect(0, 0, 3, 2),
+                rect(0, 0, 2, 1),
+                rect(0, 0, 1, 1)
+            ],
+            bounds
+        );
+        // Level 1 covers the parent image (0, 0, 6x4)
+        assert_eq!(vec![0u64..6], level_ranges(&pyramid, 1));
+        assert_eq!(vec![0u64..1], level_ranges(&pyramid, 3));
+    }
+
+    #[test]
+    fn rules_select_by_coverage() {
+        let all = MaskPyramid::try_new(mask(), DownsampleRule::All, 1).unwrap();
+        // Only the block at parent (4, 2) is fully covered
+        assert_eq!(vec![5u64..6], level_ranges(&all, 1));
+        let majority = MaskPyramid::try_new(mask(), DownsampleRule::Majority, 1).unwrap();
+        assert_eq!(vec![4u64..6], level_ranges(&majority, 1));
+    }
+
+    #[test]
+    fn query_viewport() {
+        let pyramid = MaskPyramid::try_new(mask(), DownsampleRule::Any, 1).unwrap();
+        let viewport = Rect::new(2, 1, NonZero::new(2).unwrap(), NonZero::new(2).unwrap());
+        let query = pyramid.query(1, viewport).unwrap();
+        assert_eq!(viewport, query.bounds());
+        assert_eq!(vec![0u64..1], query.collect::<Vec<_>>());
+        assert!(pyramid.query(2, viewport).is_none());
+    }
+}
@@ -0,0 +1,345 @@
+use std::{fmt::Display, num::NonZeroU32, ops::Range};
+
+use crate::{
+    BuildError, Rect, SortedRanges, UncheckedCast,
+    orientation::{RowSpan, join_row_spans, split_into_row_spans},
+};
+
+/// Resampling of the mask content to a new ROI size. The ROI offset is scaled by the same factor and
+/// rounded down, so masks are only placed exactly, if the offset is a multiple of the scale factor.
+impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+{
+    /// Nearest-neighbour sampling at the pixel centers. Integer upscaling repeats each pixel
+    /// exactly `factor` times in both directions.
+    /// ```
+    /// use std::{num::NonZero, ops::Range};
+    /// use imask::{ImageDimension, Rect, SortedRanges};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let roi = Rect::new(1, 1, NonZero::new(2).unwrap(), NonZero::new(2).unwrap());
+    /// // #.
+    /// // .#
+    /// let ranges = SortedRanges::<u16, u16>::try_from_ordered_iter_roi([0u32..1, 3..4], roi)?;
+    /// let size = NonZero::new(4).unwrap();
+    /// let resized = ranges.resize(size, size)?;
+    /// assert_eq!(Rect::new(2, 2, size, size), resized.bounds());
+    /// assert_eq!(
+    ///     vec![0u64..2, 4..6, 10..12, 14..16],
+    ///     resized.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resize(
+        &self,
+        new_width: NonZeroU32,
+        new_height: NonZeroU32,
+    ) -> Result<Self, BuildError> {
+        let (w, h) = (u64::from(self.bounds.width.get()), self.bounds.height.get());
+        let (new_w, new_h) = (u64::from(new_width.get()), u64::from(new_height.get()));
+        let rows = RowIndex::new(self);
+        // Smallest output column, whose center lies at or after source column `x`
+        let first_column = |x: u32| ((2 * u64::from(x) * new_w).saturating_sub(w)).div_ceil(2 * w);
+
+        let spans = (0..new_height.get()).flat_map(|y| {
+            let source_y = ((2 * u64::from(y) + 1) * u64::from(h) / (2 * new_h)) as u32;
+            rows.row(source_y).iter().filter_map(move |s| {
+                let start = first_column(s.start) as u32;
+                let end = first_column(s.end) as u32;
+                (start < end).then_some(RowSpan {
+                    y,
+                    start,
+                    end,
+                    meta: (),
+                })
+            })
+        });
+        self.build_resized(spans, new_width, new_height)
+    }
+
+    /// Area based resampling. A output pixel is set, if the fraction of its area covered by the
+    /// source mask exceeds `threshold` (`0.0..1.0`). The coverage is calculated from the overlap
+    /// of the runs with the output grid, so the cost depends on the number of ranges, not on the area.
+    /// ```
+    /// use std::{num::NonZero, ops::Range};
+    /// use imask::{Rect, SortedRanges};
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let size = NonZero::new(4).unwrap();
+    /// // ###.
+    /// // #...
+    /// // ....
+    /// // ...#
+    /// let ranges = SortedRanges::<u16, u16>::try_from_ordered_iter_roi(
+    ///     [0u32..3, 4..5, 15..16],
+    ///     Rect::new(0, 0, size, size),
+    /// )?;
+    /// let half = NonZero::new(2).unwrap();
+    /// let any = ranges.resize_coverage(half, half, 0.0)?;
+    /// assert_eq!(vec![0u64..2, 3..4], any.iter_roi::<Range<u64>>().collect::<Vec<_>>());
+    /// let majority = ranges.resize_coverage(half, half, 0.5)?;
+    /// assert_eq!(vec![0u64..1], majority.iter_roi::<Range<u64>>().collect::<Vec<_>>());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resize_coverage(
+        &self,
+        new_width: NonZeroU32,
+        new_height: NonZeroU32,
+        threshold: f64,
+    ) -> Result<Self, BuildError> {
+        let (w, h) = (
+            u64::from(self.bounds.width.get()),
+            u64::from(self.bounds.height.get()),
+        );
+        let (new_w, new_h) = (u64::from(new_width.get()), u64::from(new_height.get()));
+        let rows = RowIndex::new(self);
+        // Lengths are measured in units of 1/new_w (x) and 1/new_h (y) source pixels.
+        // A output pixel therefore covers w * h units.
+        let min_area = threshold * (w * h) as f64;
+
+        let mut events: Vec<(u64, i128)> = Vec::new();
+        let mut spans = Vec::new();
+        for y in 0..new_height.get() {
+            let (top, bottom) = (u64::from(y) * h, (u64::from(y) + 1) * h);
+            let source_rows = (top / new_h) as u32..bottom.div_ceil(new_h) as u32;
+            events.clear();
+            for source_y in source_rows {
+                let row_top = u64::from(source_y) * new_h;
+                let weight = (bottom.min(row_top + new_h) - top.max(row_top)) as i128;
+                for s in rows.row(source_y) {
+                    add_coverage_events(&mut events, s, new_w, w, weight);
+                }
+            }
+            events.sort_unstable_by_key(|(x, _)| *x);
+            collect_covered(&events, min_area, y, &mut spans);
+        }
+        self.build_resized(spans, new_width, new_height)
+    }
+
+    fn build_resized(
+        &self,
+        spans: impl IntoIterator<Item = RowSpan<()>>,
+        new_width: NonZeroU32,
+        new_height: NonZeroU32,
+    ) -> Result<Self, BuildError> {
+        let scale = |v: u32, from: NonZeroU32, to: NonZeroU32| {
+            let offset = u64::from(v) * u64::from(to.get()) / u64::from(from.get());
+            u32::try_from(offset).map_err(|_| BuildError::OffsetOverflow { offset })
+        };
+        let bounds = Rect::new(
+            scale(self.bounds.x, self.bounds.width, new_width)?,
+            scale(self.bounds.y, self.bounds.height, new_height)?,
+            new_width,
+            new_height,
+        );
+        let ranges = join_row_spans(spans, new_width);
+        Self::try_from_ordered_iter_roi(ranges.into_iter().map(|(r, ())| r), bounds)
+    }
+}
+
+/// Adds the horizontal coverage of `span` to the output columns as `(column, delta)` events.
+/// Coverage is piecewise constant between two events, so a column is affected by all previous deltas.
+/// Partially covered columns at the edges get their own events.
+fn add_coverage_events(
+    events: &mut Vec<(u64, i128)>,
+    span: &RowSpan<()>,
+    new_w: u64,
+    w: u64,
+    weight: i128,
+) {
+    let (start, end) = (u64::from(span.start) * new_w, u64::from(span.end) * new_w);
+    let (first, last) = (start / w, (end - 1) / w);
+    let mut point = |column: u64, len: u64| {
+        events.push((column, len as i128 * weight));
+        events.push((column + 1, -(len as i128) * weight));
+    };
+    if first == last {
+        point(first, end - start);
+        return;
+    }
+    point(first, (first + 1) * w - start);
+    point(last, end - last * w);
+    if first + 1 < last {
+        events.push((first + 1, w as i128 * weight));
+        events.push((last, -(w as i128) * weight));
+    }
+}
+
+fn collect_covered(events: &[(u64, i128)], min_area: f64, y: u32, out: &mut Vec<RowSpan<()>>) {
+    let mut level = 0i128;
+    let mut i = 0;
+    while i < events.len() {
+        let column = events[i].0;
+        while i < events.len() && events[i].0 == column {
+            level += events[i].1;
+            i += 1;
+        }
+        let Some(&(next, _)) = events.get(i) else {
+            break;
+        };
+        if level as f64 > min_area {
+            let (start, end) = (column as u32, next as u32);
+            match out.last_mut() {
+                Some(last) if last.y == y && last.end == start => last.end = end,
+                _ => out.push(RowSpan {
+                    y,
+                    start,
+                    end,
+                    meta: (),
+                }),
+            }
+        }
+    }
+}
+
+/// Spans of a SortedRanges, which can be accessed by row
+struct RowIndex {
+    spans: Vec<RowSpan<()>>,
+    row_starts: Vec<usize>,
+}
+
+impl RowIndex {
+    fn new<TIncluded, TExcluded>(ranges: &SortedRanges<TIncluded, TExcluded>) -> Self
+    where
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+    {
+        let spans = split_into_row_spans(
+            ranges.iter_roi::<Range<u64>>().map(|r| (r, ())),
+            ranges.bounds.width,
+        );
+        let height = ranges.bounds.height.get() as usize;
+        let mut row_starts = Vec::with_capacity(height + 1);
+        let mut pos = 0;
+        for y in 0..=height as u32 {
+            while spans.get(pos).is_some_and(|s| s.y < y) {
+                pos += 1;
+            }
+            row_starts.push(pos);
+        }
+        Self { spans, row_starts }
+    }
+
+    fn row(&self, y: u32) -> &[RowSpan<()>] {
+        let Range { start, end } = self.row_range(y);
+        &self.spans[start..end]
+    }
+
+    fn row_range(&self, y: u32) -> Range<usize> {
+        let y = y as usize;
+        match (self.row_starts.get(y), self.row_starts.get(y + 1)) {
+            (Some(&start), Some(&end)) => start..end,
+            _ => 0..0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use crate::{ImageDimension, Rect};
+
+    use super::*;
+
+    const SIZE: NonZeroU32 = NonZero::new(8).unwrap();
+    const QUARTER: NonZeroU32 = NonZero::new(2).unwrap();
+
+    fn collect(ranges: &SortedRanges<u16, u16>) -> Vec<Range<u64>> {
+        ranges.iter_roi().collect()
+    }
+
+    #[test]
+    fn upsample_by_integer_factor() {
+        let ranges = SortedRanges::<u16, u16>::try_from_ordered_iter_roi(
+            std::iter::once(1u32..3),
+            Rect::new(0, 0, QUARTER, QUARTER),
+        )
+        .unwrap();
+        let resized = ranges.resize(SIZE, SIZE).unwrap();
+        assert_eq!(Rect::new(0, 0, SIZE, SIZE), resized.bounds());
+        assert_eq!(
+            vec![4..8, 12..16, 20..24, 28..36, 40..44, 48..52, 56..60],
+            collect(&resized)
+        );
+    }
+
+    #[test]
+    fn resize_roundtrip_restores_original() {
+        let ranges = SortedRanges::<u16, u16>::try_from_ordered_iter_roi(
+            [3u32..10, 20..22, 40..41, 63..64],
+            Rect::new(0, 0, SIZE, SIZE),
+        )
+        .unwrap();
+        let big = NonZero::new(24).unwrap();
+        let upsampled = ranges.resize(big, big).unwrap();
+        assert_eq!(ranges, upsampled.resize(SIZE, SIZE).unwrap());
+        assert_eq!(ranges, upsampled.resize_coverage(SIZE, SIZE, 0.5).unwrap());
+    }
+
+    #[test]
+    fn downsample_nearest_samples_centers() {
+        // Only the pixel at (1, 1) of each 2x2 block is sampled
+        let ranges = SortedRanges::<u16, u16>::try_from_ordered_iter_roi(
+            [0u32..1, 9..10, 18..19],
+            Rect::new(0, 0, SIZE, SIZE),
+        )
+        .unwrap();
+        let half = NonZero::new(4).unwrap();
+        let resized = ranges.resize(half, half).unwrap();
+        assert_eq!(vec![0..1], collect(&resized));
+    }
+
+    #[test]
+    fn coverage_with_non_integer_factor() {
+        // 3x1 -> 2x1: every output pixel covers 1.5 source pixels
+        let three = NonZero::new(3).unwrap();
+        let ranges = SortedRanges::<u16, u16>::try_from_ordered_iter_roi(
+            std::iter::once(0u32..1),
+            Rect::new(0, 0, three, NonZero::new(1).unwrap()),
+        )
+        .unwrap();
+        let one = NonZero::new(1).unwrap();
+        let resized = ranges.resize_coverage(QUARTER, one, 0.6).unwrap();
+        assert_eq!(vec![0..1], collect(&resized));
+        let resized = ranges.resize_coverage(QUARTER, one, 0.7).unwrap();
+        assert!(resized.is_empty());
+    }
+
+    #[test]
+    fn offset_is_scaled() {
+        let roi = Rect::new(4, 2, QUARTER, QUARTER);
+        let ranges =
+            SortedRanges::<u16, u16>::try_from_ordered_iter_roi(std::iter::once(0u32..4), roi)
+                .unwrap();
+        let resized = ranges.resize_coverage(SIZE, SIZE, 0.5).unwrap();
+        assert_eq!(Rect::new(16, 8, SIZE, SIZE), resized.bounds());
+        assert_eq!(vec![0..64], collect(&resized));
+    }
+
+    #[test]
+    fn overflowing_offset_causes_error() {
+        let one = NonZero::new(1).unwrap();
+        let roi = Rect::new(3_000_000_000, 0, one, one);
+        let ranges =
+            SortedRanges::<u16, u16>::try_from_ordered_iter_roi(std::iter::once(0u32..1), roi)
+                .unwrap();
+        let two = NonZero::new(2).unwrap();
+        assert_eq!(
+            Err(BuildError::OffsetOverflow {
+                offset: 6_000_000_000
+            }),
+            ranges.resize(two, one)
+        );
+        assert_eq!(
+            Err(BuildError::OffsetOverflow {
+                offset: 6_000_000_000
+            }),
+            ranges.resize_coverage(two, one, 0.5)
+        );
+    }
+}
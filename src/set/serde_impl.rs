@@ -0,0 +1,249 @@
+use std::{fmt::Display, ops::Range};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+
+use crate::{BuildError, Rect, SortedRanges, UncheckedCast};
+
+#[derive(Serialize)]
+struct CompactRef<'a, TIncluded, TExcluded> {
+    bounds: Rect<u32>,
+    excluded: &'a [TExcluded],
+    included: &'a [TIncluded],
+}
+
+#[derive(Deserialize)]
+struct Compact<TIncluded, TExcluded> {
+    bounds: Rect<u32>,
+    excluded: Vec<TExcluded>,
+    included: Vec<TIncluded>,
+}
+
+/// Decodes gaps and lengths like `SortedRangesIter`: Zero lengths continue the gap, zero gaps
+/// continue the length
+fn decode<TIncluded, TExcluded>(excluded: &[TExcluded], included: &[TIncluded]) -> Vec<Range<u64>>
+where
+    TIncluded: UncheckedCast<u64>,
+    TExcluded: UncheckedCast<u64>,
+{
+    let mut ranges: Vec<Range<u64>> = Vec::with_capacity(included.len());
+    let mut pos = 0u64;
+    for (&e, &i) in excluded.iter().zip(included) {
+        let (e, i): (u64, u64) = (e.cast_unchecked(), i.cast_unchecked());
+        pos = pos.saturating_add(e);
+        match ranges.last_mut() {
+            Some(last) if e == 0 && last.end == pos => last.end = last.end.saturating_add(i),
+            _ if i == 0 => continue,
+            _ => ranges.push(pos..pos.saturating_add(i)),
+        }
+        pos = pos.saturating_add(i);
+    }
+    ranges
+}
+
+/// Compact representation: The ROI and the raw arrays of gaps and lengths.
+/// Use `serde_ranges` for a human readable alternative
+impl<TIncluded: Serialize, TExcluded: Serialize> Serialize for SortedRanges<TIncluded, TExcluded> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CompactRef {
+            bounds: self.bounds,
+            excluded: &self.excluded,
+            included: &self.included,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Decodes continuation entries like the iterators and re-encodes the ranges with
+/// `try_from_ordered_iter_roi_split`, so split encodings round-trip
+impl<'de, TIncluded, TExcluded> Deserialize<'de> for SortedRanges<TIncluded, TExcluded>
+where
+    TIncluded:
+        Deserialize<'de> + UncheckedCast<u64> + TryFrom<u64, Error: Display> + num_traits::Bounded,
+    TExcluded:
+        Deserialize<'de> + UncheckedCast<u64> + TryFrom<u64, Error: Display> + num_traits::Bounded,
+    u64: UncheckedCast<TIncluded> + UncheckedCast<TExcluded>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let compact = Compact::<TIncluded, TExcluded>::deserialize(deserializer)?;
+        if compact.included.len() != compact.excluded.len() {
+            return Err(D::Error::custom(BuildError::LengthMismatch {
+                included: compact.included.len(),
+                excluded: compact.excluded.len(),
+            }));
+        }
+        let ranges = decode(&compact.excluded, &compact.included);
+        Self::try_from_ordered_iter_roi_split(ranges, compact.bounds).map_err(D::Error::custom)
+    }
+}
+
+/// Human readable representation of `SortedRanges` as ROI and `[[start, end], ...]` relative to
+/// the ROI. Use it with `#[serde(with = "imask::serde_ranges")]`
+/// ```
+/// use std::num::NonZero;
+/// use imask::{Rect, SortedRanges};
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Annotation {
+///     #[serde(with = "imask::serde_ranges")]
+///     mask: SortedRanges<u16, u16>,
+/// }
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let json = r#"{"mask":{"bounds":{"x":0,"y":0,"width":10,"height":10},"ranges":[[3,5],[8,16]]}}"#;
+/// let annotation: Annotation = serde_json::from_str(json)?;
+/// assert_eq!(2, annotation.mask.len());
+/// assert_eq!(json, serde_json::to_string(&annotation)?);
+/// # Ok(())
+/// # }
+/// ```
+pub mod serde_ranges {
+    use super::*;
+
+    struct RangesRef<'a, TIncluded, TExcluded>(&'a SortedRanges<TIncluded, TExcluded>);
+
+    impl<TIncluded, TExcluded> Serialize for RangesRef<'_, TIncluded, TExcluded>
+    where
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.iter_roi::<Range<u64>>().map(|r| [r.start, r.end]))
+        }
+    }
+
+    #[derive(Serialize)]
+    #[serde(bound = "RangesRef<'a, TIncluded, TExcluded>: Serialize")]
+    struct ReadableRef<'a, TIncluded, TExcluded> {
+        bounds: Rect<u32>,
+        ranges: RangesRef<'a, TIncluded, TExcluded>,
+    }
+
+    #[derive(Deserialize)]
+    struct Readable {
+        bounds: Rect<u32>,
+        ranges: Vec<(u64, u64)>,
+    }
+
+    pub fn serialize<S, TIncluded, TExcluded>(
+        ranges: &SortedRanges<TIncluded, TExcluded>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+    {
+        ReadableRef {
+            bounds: ranges.bounds,
+            ranges: RangesRef(ranges),
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D, TIncluded, TExcluded>(
+        deserializer: D,
+    ) -> Result<SortedRanges<TIncluded, TExcluded>, D::Error>
+    where
+        D: Deserializer<'de>,
+        TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display> + num_traits::Bounded,
+        TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display> + num_traits::Bounded,
+        u64: UncheckedCast<TIncluded> + UncheckedCast<TExcluded>,
+    {
+        let readable = Readable::deserialize(deserializer)?;
+        let ranges = readable.ranges.into_iter().map(|(s, e)| s..e);
+        SortedRanges::try_from_ordered_iter_roi_split(ranges, readable.bounds)
+            .map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZero;
+
+    use super::*;
+
+    #[test]
+    fn compact_roundtrip() {
+        let size = NonZero::new(10).unwrap();
+        let ranges = SortedRanges::<u8, u16>::try_from_ordered_iter_roi(
+            [3u32..5, 8..16],
+            Rect::new(1, 2, size, size),
+        )
+        .unwrap();
+        let json = serde_json::to_string(&ranges).unwrap();
+        assert_eq!(
+            r#"{"bounds":{"x":1,"y":2,"width":10,"height":10},"excluded":[3,3],"included":[2,8]}"#,
+            json
+        );
+        assert_eq!(
+            ranges,
+            serde_json::from_str::<SortedRanges<u8, u16>>(&json).unwrap()
+        );
+    }
+
+    #[test]
+    fn reject_invalid_payloads() {
+        let parse = |data: &str| {
+            let json = format!(r#"{{"bounds":{{"x":0,"y":0,"width":10,"height":10}},{data}}}"#);
+            serde_json::from_str::<SortedRanges<u8, u8>>(&json)
+                .unwrap_err()
+                .to_string()
+        };
+        assert!(parse(r#""excluded":[1],"included":[1,1]"#).contains("excluded values"));
+        assert!(parse(r#""excluded":[1],"included":[100]"#).contains("only contains 100"));
+        assert!(parse(r#""excluded":[1],"included":[256]"#).contains("invalid value"));
+    }
+
+    #[test]
+    fn continuations_are_decoded_like_the_iterator() {
+        let parse = |data: &str| {
+            let json = format!(r#"{{"bounds":{{"x":0,"y":0,"width":10,"height":10}},{data}}}"#);
+            let ranges = serde_json::from_str::<SortedRanges<u8, u8>>(&json).unwrap();
+            ranges.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+        };
+        // A zero length continues the gap, a zero gap continues the length
+        assert_eq!(vec![3..5], parse(r#""excluded":[1,2],"included":[0,2]"#));
+        assert_eq!(vec![1..3], parse(r#""excluded":[1,0],"included":[1,1]"#));
+        assert!(parse(r#""excluded":[1],"included":[0]"#).is_empty());
+    }
+
+    #[test]
+    fn crate_built_masks_roundtrip() {
+        let size = NonZero::new(1000).unwrap();
+        let bounds = Rect::new(0, 0, size, size);
+        let split = SortedRanges::<u8, u8>::try_from_ordered_iter_roi_split(
+            [10u32..20, 600..1500, 1800..1801],
+            bounds,
+        )
+        .unwrap();
+        let json = serde_json::to_string(&split).unwrap();
+        assert_eq!(split, serde_json::from_str(&json).unwrap());
+        let mut serializer = serde_json::Serializer::new(Vec::new());
+        serde_ranges::serialize(&split, &mut serializer).unwrap();
+        let json = serializer.into_inner();
+        let mut deserializer = serde_json::Deserializer::from_slice(&json);
+        assert_eq!(
+            split,
+            serde_ranges::deserialize::<_, u8, u8>(&mut deserializer).unwrap()
+        );
+
+        // The builder rejects what the deserializer would reject
+        let small = Rect::new(0, 0, NonZero::new(2).unwrap(), NonZero::new(2).unwrap());
+        assert_eq!(
+            Err(BuildError::OutOfBounds { end: 14, size: 4 }),
+            SortedRanges::<u8, u8>::try_from_ordered_iter_roi([0u32..10, 12..14], small)
+        );
+        let regular =
+            SortedRanges::<u8, u8>::try_from_ordered_iter_roi([0u32..1, 2..4], small).unwrap();
+        let json = serde_json::to_string(&regular).unwrap();
+        assert_eq!(regular, serde_json::from_str(&json).unwrap());
+    }
+
+    #[test]
+    fn readable_rejects_overlapping_ranges() {
+        let json = r#"{"bounds":{"x":0,"y":0,"width":10,"height":10},"ranges":[[3,5],[4,6]]}"#;
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        let error = serde_ranges::deserialize::<_, u8, u8>(&mut deserializer).unwrap_err();
+        assert!(error.to_string().contains("overlaps"), "{error}");
+    }
+}
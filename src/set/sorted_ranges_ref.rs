@@ -0,0 +1,243 @@
+use std::{
+    fmt::Debug,
+    num::{NonZero, NonZeroU32},
+    ops::{Add, Div, Mul, Rem, Sub},
+};
+
+use crate::{
+    BuildError, CreateRange, ImageDimension, Rect, SignedNonZeroable, SortedRanges,
+    SortedRangesIter, SortedRangesIterGlobal, UncheckedCast, build_error::check_bounds,
+};
+
+type CopiedSliceIter<'a, T> = std::iter::Copied<std::slice::Iter<'a, T>>;
+
+/// Borrowed `SortedRanges`, e.g. for masks within memory mapped files or arena allocations.
+/// Uses the same encoding as `SortedRanges`, including split continuation entries
+/// ```
+/// use std::{num::NonZero, ops::Range};
+/// use imask::{Rect, SortedRangesRef};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let size = NonZero::new(10).unwrap();
+/// let buffer = [2u8, 8, 3, 3, 3, 5];
+/// let (included, excluded) = buffer.split_at(3);
+/// let ranges = SortedRangesRef::try_new(included, excluded, Rect::new(0, 0, size, size))?;
+/// assert_eq!(
+///     vec![3u32..5, 8..16, 21..24],
+///     ranges.iter_roi::<Range<u32>>().collect::<Vec<_>>()
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub struct SortedRangesRef<'a, TIncluded, TExcluded> {
+    included: &'a [TIncluded],
+    excluded: &'a [TExcluded],
+    bounds: Rect<u32>,
+}
+
+impl<TIncluded, TExcluded> Clone for SortedRangesRef<'_, TIncluded, TExcluded> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<TIncluded, TExcluded> Copy for SortedRangesRef<'_, TIncluded, TExcluded> {}
+
+impl<TIncluded, TExcluded> Debug for SortedRangesRef<'_, TIncluded, TExcluded> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SortedRangesRef")
+            .field("range_count", &self.included.len())
+            .field("bounds", &self.bounds)
+            .finish()
+    }
+}
+
+impl<'a, TIncluded, TExcluded> SortedRangesRef<'a, TIncluded, TExcluded> {
+    /// Checks, that both slices have the same length and all ranges lie within `bounds`
+    pub fn try_new(
+        included: &'a [TIncluded],
+        excluded: &'a [TExcluded],
+        bounds: Rect<u32>,
+    ) -> Result<Self, BuildError>
+    where
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+    {
+        if included.len() != excluded.len() {
+            return Err(BuildError::LengthMismatch {
+                included: included.len(),
+                excluded: excluded.len(),
+            });
+        }
+        let end = included
+            .iter()
+            .zip(excluded)
+            .try_fold(0u64, |end, (&i, &e)| {
+                end.checked_add(i.cast_unchecked())?
+                    .checked_add(e.cast_unchecked())
+            })
+            .unwrap_or(u64::MAX);
+        check_bounds(end, bounds)?;
+        Ok(Self::new_unchecked(included, excluded, bounds))
+    }
+
+    /// Trusts the caller to provide valid slices. Invalid slices don't cause undefined behavior,
+    /// but iterators might return garbage or panic
+    pub fn new_unchecked(
+        included: &'a [TIncluded],
+        excluded: &'a [TExcluded],
+        bounds: Rect<u32>,
+    ) -> Self {
+        debug_assert_eq!(included.len(), excluded.len());
+        Self {
+            included,
+            excluded,
+            bounds,
+        }
+    }
+
+    pub fn included(&self) -> &'a [TIncluded] {
+        self.included
+    }
+
+    pub fn excluded(&self) -> &'a [TExcluded] {
+        self.excluded
+    }
+
+    pub fn to_owned(&self) -> SortedRanges<TIncluded, TExcluded>
+    where
+        TIncluded: Clone,
+        TExcluded: Clone,
+    {
+        SortedRanges {
+            included: self.included.to_vec(),
+            excluded: self.excluded.to_vec(),
+            bounds: self.bounds,
+        }
+    }
+
+    /// Returns the number of ranges. Continuation entries of split encodings count separately
+    pub fn len(&self) -> usize {
+        self.included.len()
+    }
+
+    /// Returns the number of ranges or None, if the mask is empty
+    pub fn len_nonzero(&self) -> Option<NonZero<usize>> {
+        NonZero::new(self.included.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.included.is_empty()
+    }
+
+    pub fn iter_roi<T: CreateRange>(
+        &self,
+    ) -> SortedRangesIter<CopiedSliceIter<'a, TIncluded>, CopiedSliceIter<'a, TExcluded>, T>
+    where
+        TIncluded: UncheckedCast<T::Item>,
+        TExcluded: UncheckedCast<T::Item>,
+        T::Item: Default + Copy + SignedNonZeroable + Add<Output = T::Item>,
+    {
+        SortedRangesIter::new(
+            self.included.iter().copied(),
+            self.excluded.iter().copied(),
+            T::Item::default(),
+            self.bounds.width,
+            self.bounds.height,
+        )
+    }
+
+    pub fn iter_global_with<T: CreateRange>(
+        &self,
+        width: NonZeroU32,
+    ) -> SortedRangesIterGlobal<CopiedSliceIter<'a, TIncluded>, CopiedSliceIter<'a, TExcluded>, T>
+    where
+        TIncluded: UncheckedCast<T::Item>,
+        TExcluded: UncheckedCast<T::Item>,
+        T::Item: Default
+            + Copy
+            + SignedNonZeroable
+            + Add<Output = T::Item>
+            + Sub<Output = T::Item>
+            + Mul<Output = T::Item>
+            + Div<Output = T::Item>
+            + Rem<Output = T::Item>
+            + Ord,
+        u32: UncheckedCast<T::Item>,
+    {
+        SortedRangesIterGlobal::new(
+            self.included.iter().copied(),
+            self.excluded.iter().copied(),
+            self.bounds,
+            width,
+        )
+    }
+}
+
+impl<'a, TIncluded, TExcluded> From<&'a SortedRanges<TIncluded, TExcluded>>
+    for SortedRangesRef<'a, TIncluded, TExcluded>
+{
+    fn from(value: &'a SortedRanges<TIncluded, TExcluded>) -> Self {
+        Self::new_unchecked(&value.included, &value.excluded, value.bounds)
+    }
+}
+
+impl<TIncluded, TExcluded> ImageDimension for SortedRangesRef<'_, TIncluded, TExcluded> {
+    fn bounds(&self) -> Rect<u32> {
+        self.bounds
+    }
+    fn width(&self) -> NonZero<u32> {
+        self.bounds.width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use super::*;
+
+    const SIZE: NonZeroU32 = NonZero::new(10).unwrap();
+    const BOUNDS: Rect<u32> = Rect::new(0, 2, SIZE, SIZE);
+
+    #[test]
+    fn borrow_and_convert_back() {
+        let ranges =
+            SortedRanges::<u8, u16>::try_from_ordered_iter_roi([3u32..5, 8..16], BOUNDS).unwrap();
+        let borrowed = SortedRangesRef::from(&ranges);
+        assert_eq!(BOUNDS, borrowed.bounds());
+        assert_eq!(&[2u8, 8], borrowed.included());
+        assert_eq!(
+            ranges.iter_roi::<Range<u64>>().collect::<Vec<_>>(),
+            borrowed.iter_roi::<Range<u64>>().collect::<Vec<_>>()
+        );
+        let width = NonZero::new(20).unwrap();
+        assert_eq!(
+            ranges
+                .iter_global_with::<Range<u64>>(width)
+                .collect::<Vec<_>>(),
+            borrowed
+                .iter_global_with::<Range<u64>>(width)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(ranges, borrowed.to_owned());
+    }
+
+    #[test]
+    fn validate_slices() {
+        assert_eq!(
+            BuildError::LengthMismatch {
+                included: 2,
+                excluded: 1
+            },
+            SortedRangesRef::try_new(&[1u8, 2], &[0u8], BOUNDS).unwrap_err()
+        );
+        assert_eq!(
+            BuildError::OutOfBounds {
+                end: 101,
+                size: 100
+            },
+            SortedRangesRef::try_new(&[1u8], &[100u8], BOUNDS).unwrap_err()
+        );
+        assert!(SortedRangesRef::try_new(&[1u8], &[99u8], BOUNDS).is_ok());
+    }
+}
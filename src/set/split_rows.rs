@@ -1,6 +1,6 @@
 use std::{fmt::Debug, iter::FusedIterator, marker::PhantomData, num::NonZero};
 
-use crate::{CreateRange, ImageDimension, SignedNonZeroable, UncheckedCast};
+use crate::{CreateRange, ImageDimension, UncheckedCast};
 
 pub struct SplitRowsIter<T, R> {
     parent: T,
@@ -10,8 +10,6 @@ pub struct SplitRowsIter<T, R> {
 
 impl<T: ImageDimension, R> SplitRowsIter<T, R> {
     pub fn new(parent: T) -> Self {
-        assert_eq!(parent.bounds().x, 0);
-        assert_eq!(parent.bounds().y, 0);
         Self {
             parent,
             pending: None,
@@ -57,10 +55,8 @@ where
         if end <= next_row_start {
             Some(range)
         } else {
-            let remaining_len = end - next_row_start;
-
-            self.pending = Some(R::new_debug_checked_zeroable(next_row_start, remaining_len));
-            Some(R::new_debug_checked_zeroable(start, next_row_start - start))
+            self.pending = Some(R::new_debug_checked_zeroable(next_row_start, end));
+            Some(R::new_debug_checked_zeroable(start, next_row_start))
         }
     }
 }
@@ -103,6 +99,7 @@ mod range_set_blaze_0_5_interop {
 }
 
 #[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
 mod tests {
     use std::{num::NonZero, ops::Range};
 
@@ -196,4 +193,12 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn roi_with_offset() {
+        let roi = crate::Rect::new(7, 3, WIDTH_U32, WIDTH_U32);
+        let split = [8u32..23].with_roi(roi).split_rows();
+        assert_eq!(split.bounds(), roi);
+        assert_eq!(split.collect::<Vec<_>>(), vec![8..10, 10..20, 20..23]);
+    }
 }
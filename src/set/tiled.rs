@@ -0,0 +1,421 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap},
+    fmt::Display,
+    iter::FusedIterator,
+    num::{NonZero, NonZeroU32},
+    ops::Range,
+};
+
+use crate::{BuildError, ImageDimension, Rect, SortedRanges, SortedRangesIter, UncheckedCast};
+
+/// Position of a tile within a `TileGrid`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TileIndex {
+    pub row: u32,
+    pub column: u32,
+}
+
+/// Splits an image into square tiles of `tile_size`, starting at the image origin.
+/// Tiles at the right and bottom border are cropped to the image
+/// ```
+/// use std::{num::NonZero, ops::Range};
+/// use imask::{ImaskSet, Rect, SortedRanges, TileGrid, TileIndex};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let size = NonZero::new(20).unwrap();
+/// let grid = TileGrid::new(size, size, NonZero::new(8).unwrap());
+/// let mask = SortedRanges::<u16, u16>::try_from_ordered_iter([5u32..15, 395..400].with_bounds(size, size))?;
+/// let tiles = mask.split_into_tiles(grid.tile_size())?;
+/// assert_eq!(
+///     vec![TileIndex { row: 0, column: 0 }, TileIndex { row: 0, column: 1 }, TileIndex { row: 2, column: 1 }, TileIndex { row: 2, column: 2 }],
+///     tiles.keys().copied().collect::<Vec<_>>()
+/// );
+///
+/// // Only reads tiles intersecting the query
+/// let mut loaded = Vec::new();
+/// let query = Rect::new(0, 0, size, NonZero::new(2).unwrap());
+/// let stitched = grid.load(
+///     &mut |index: TileIndex| {
+///         loaded.push(index);
+///         Ok::<_, std::convert::Infallible>(tiles.get(&index).cloned())
+///     },
+///     query,
+/// )?;
+/// assert_eq!(vec![5u64..15], stitched.collect::<Vec<_>>());
+/// assert_eq!(3, loaded.len());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileGrid {
+    width: NonZeroU32,
+    height: NonZeroU32,
+    tile_size: NonZeroU32,
+}
+
+impl TileGrid {
+    pub const fn new(width: NonZeroU32, height: NonZeroU32, tile_size: NonZeroU32) -> Self {
+        Self {
+            width,
+            height,
+            tile_size,
+        }
+    }
+
+    pub fn tile_size(&self) -> NonZeroU32 {
+        self.tile_size
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.width.get().div_ceil(self.tile_size.get())
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.height.get().div_ceil(self.tile_size.get())
+    }
+
+    /// Area of the tile within the image
+    /// # Panics
+    /// If `index` is outside of the grid
+    pub fn tile_bounds(&self, index: TileIndex) -> Rect<u32> {
+        assert!(
+            index.column < self.columns() && index.row < self.rows(),
+            "{index:?} is outside of the grid"
+        );
+        let size = self.tile_size.get();
+        let (x, y) = (index.column * size, index.row * size);
+        Rect::new(
+            x,
+            y,
+            NonZero::new(size.min(self.width.get() - x)).expect("x is within the image"),
+            NonZero::new(size.min(self.height.get() - y)).expect("y is within the image"),
+        )
+    }
+
+    /// Indices of all tiles overlapping `query` in row major order
+    pub fn tiles_intersecting(&self, query: Rect<u32>) -> impl Iterator<Item = TileIndex> + use<> {
+        let size = self.tile_size.get();
+        let columns = query.x / size..query.len_x().get().div_ceil(size).min(self.columns());
+        let rows = query.y / size..query.len_y().get().div_ceil(size).min(self.rows());
+        rows.flat_map(move |row| columns.clone().map(move |column| TileIndex { row, column }))
+    }
+
+    /// Reads the tiles overlapping `query` and stitches them. The result contains all ranges
+    /// of these tiles. Use `ImaskSet::try_clip_2d` to restrict it to `query`
+    pub fn load<TIncluded, TExcluded, TLoader>(
+        &self,
+        loader: &mut TLoader,
+        query: Rect<u32>,
+    ) -> Result<StitchIter<TIncluded, TExcluded>, TLoader::Error>
+    where
+        TLoader: TileLoader<TIncluded, TExcluded>,
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+    {
+        let mut tiles = Vec::new();
+        for index in self.tiles_intersecting(query) {
+            tiles.extend(loader.load_tile(index)?);
+        }
+        Ok(self.stitch(tiles))
+    }
+
+    /// Merges disjoint tile masks into ranges relative to the image. Ranges touching across tile
+    /// borders are joined. Each tile is positioned by its own bounds
+    pub fn stitch<TIncluded, TExcluded>(
+        &self,
+        tiles: impl IntoIterator<Item = SortedRanges<TIncluded, TExcluded>>,
+    ) -> StitchIter<TIncluded, TExcluded>
+    where
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+    {
+        StitchIter::new(tiles, self.width, self.height)
+    }
+}
+
+/// Reads single tiles from a container format
+pub trait TileLoader<TIncluded, TExcluded> {
+    type Error;
+    /// Returns `None` for tiles without any pixel
+    fn load_tile(
+        &mut self,
+        index: TileIndex,
+    ) -> Result<Option<SortedRanges<TIncluded, TExcluded>>, Self::Error>;
+}
+
+impl<TIncluded, TExcluded, TError, F> TileLoader<TIncluded, TExcluded> for F
+where
+    F: FnMut(TileIndex) -> Result<Option<SortedRanges<TIncluded, TExcluded>>, TError>,
+{
+    type Error = TError;
+    fn load_tile(
+        &mut self,
+        index: TileIndex,
+    ) -> Result<Option<SortedRanges<TIncluded, TExcluded>>, TError> {
+        self(index)
+    }
+}
+
+impl<TIncluded, TExcluded> SortedRanges<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+    TExcluded: UncheckedCast<u64> + TryFrom<u64, Error: Display>,
+{
+    /// Splits the mask into tiles of `tile_size`, aligned to the origin of the parent image.
+    /// Tiles without pixels are skipped. The bounds of each tile are cropped to the ROI
+    pub fn split_into_tiles(
+        &self,
+        tile_size: NonZeroU32,
+    ) -> Result<BTreeMap<TileIndex, SortedRanges<TIncluded, TExcluded>>, BuildError> {
+        let size = tile_size.get();
+        let roi = self.bounds;
+        let roi_width = u64::from(roi.width.get());
+        let mut tiles = BTreeMap::<TileIndex, Vec<Range<u64>>>::new();
+        for range in self.iter_roi::<Range<u64>>() {
+            let mut pos = range.start;
+            while pos < range.end {
+                let (row, col) = (pos / roi_width, pos % roi_width);
+                let row_end = range.end.min((row + 1) * roi_width);
+                let y = roi.y + row as u32;
+                let mut x = roi.x + col as u32;
+                let x_end = x + (row_end - pos) as u32;
+                while x < x_end {
+                    let index = TileIndex {
+                        row: y / size,
+                        column: x / size,
+                    };
+                    let piece_end = x_end.min((index.column + 1) * size);
+                    let tile = tile_bounds_in(roi, index, size);
+                    let start =
+                        u64::from(y - tile.y) * u64::from(tile.width.get()) + u64::from(x - tile.x);
+                    let end = start + u64::from(piece_end - x);
+                    let ranges = tiles.entry(index).or_default();
+                    match ranges.last_mut() {
+                        Some(last) if last.end == start => last.end = end,
+                        _ => ranges.push(start..end),
+                    }
+                    x = piece_end;
+                }
+                pos = row_end;
+            }
+        }
+        tiles
+            .into_iter()
+            .map(|(index, ranges)| {
+                let tile = tile_bounds_in(roi, index, size);
+                Ok((
+                    index,
+                    SortedRanges::try_from_ordered_iter_roi(ranges, tile)?,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Tile area cropped to `roi`
+fn tile_bounds_in(roi: Rect<u32>, index: TileIndex, size: u32) -> Rect<u32> {
+    let x = roi.x.max(index.column * size);
+    let y = roi.y.max(index.row * size);
+    let x_end = roi.len_x().get().min((index.column + 1) * size);
+    let y_end = roi.len_y().get().min((index.row + 1) * size);
+    Rect::new(
+        x,
+        y,
+        NonZero::new(x_end - x).expect("Tile intersects the ROI"),
+        NonZero::new(y_end - y).expect("Tile intersects the ROI"),
+    )
+}
+
+type OwnedTileIter<TIncluded, TExcluded> =
+    SortedRangesIter<std::vec::IntoIter<TIncluded>, std::vec::IntoIter<TExcluded>, Range<u64>>;
+
+/// Ranges of a single tile, split into rows and moved into the parent image
+struct TileRows<TIncluded, TExcluded> {
+    ranges: OwnedTileIter<TIncluded, TExcluded>,
+    bounds: Rect<u32>,
+    image_width: u64,
+    remaining: Range<u64>,
+}
+
+impl<TIncluded, TExcluded> Iterator for TileRows<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64>,
+    TExcluded: UncheckedCast<u64>,
+{
+    type Item = Range<u64>;
+
+    fn next(&mut self) -> Option<Range<u64>> {
+        if self.remaining.is_empty() {
+            self.remaining = self.ranges.next()?;
+        }
+        let width = u64::from(self.bounds.width.get());
+        let (row, col) = (self.remaining.start / width, self.remaining.start % width);
+        let end = self.remaining.end.min((row + 1) * width);
+        let start =
+            (u64::from(self.bounds.y) + row) * self.image_width + u64::from(self.bounds.x) + col;
+        let piece = start..start + (end - self.remaining.start);
+        self.remaining.start = end;
+        Some(piece)
+    }
+}
+
+/// Merges the ranges of several tiles into sorted ranges relative to the parent image
+pub struct StitchIter<TIncluded, TExcluded> {
+    tiles: Vec<TileRows<TIncluded, TExcluded>>,
+    heap: BinaryHeap<Reverse<(u64, u64, usize)>>,
+    width: NonZeroU32,
+    height: NonZeroU32,
+}
+
+impl<TIncluded, TExcluded> StitchIter<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64>,
+    TExcluded: UncheckedCast<u64>,
+{
+    fn new(
+        tiles: impl IntoIterator<Item = SortedRanges<TIncluded, TExcluded>>,
+        width: NonZeroU32,
+        height: NonZeroU32,
+    ) -> Self {
+        let mut tiles = tiles
+            .into_iter()
+            .map(|tile| TileRows {
+                bounds: tile.bounds,
+                ranges: tile.iter_roi_owned(),
+                image_width: u64::from(width.get()),
+                remaining: 0..0,
+            })
+            .collect::<Vec<_>>();
+        let heap = tiles
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, tile)| tile.next().map(|r| Reverse((r.start, r.end, i))))
+            .collect();
+        Self {
+            tiles,
+            heap,
+            width,
+            height,
+        }
+    }
+
+    fn pop(&mut self) -> Option<Range<u64>> {
+        let Reverse((start, end, tile)) = self.heap.pop()?;
+        if let Some(next) = self.tiles[tile].next() {
+            self.heap.push(Reverse((next.start, next.end, tile)));
+        }
+        Some(start..end)
+    }
+}
+
+impl<TIncluded, TExcluded> Iterator for StitchIter<TIncluded, TExcluded>
+where
+    TIncluded: UncheckedCast<u64>,
+    TExcluded: UncheckedCast<u64>,
+{
+    type Item = Range<u64>;
+
+    fn next(&mut self) -> Option<Range<u64>> {
+        let mut result = self.pop()?;
+        while let Some(Reverse((start, ..))) = self.heap.peek() {
+            debug_assert!(*start >= result.end, "Tiles must not overlap");
+            if *start != result.end {
+                break;
+            }
+            result.end = self.pop().expect("Peeked before").end;
+        }
+        Some(result)
+    }
+}
+
+impl<TIncluded, TExcluded> FusedIterator for StitchIter<TIncluded, TExcluded> where Self: Iterator {}
+
+impl<TIncluded, TExcluded> ImageDimension for StitchIter<TIncluded, TExcluded> {
+    fn bounds(&self) -> Rect<u32> {
+        Rect::new(0, 0, self.width, self.height)
+    }
+    fn width(&self) -> NonZero<u32> {
+        self.width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: NonZeroU32 = NonZero::new(10).unwrap();
+    const HEIGHT: NonZeroU32 = NonZero::new(7).unwrap();
+    const GRID: TileGrid = TileGrid::new(WIDTH, HEIGHT, NonZero::new(4).unwrap());
+
+    fn mask() -> SortedRanges<u8, u8> {
+        // ROI (1, 1, 8, 5) of the 10x7 image
+        let roi = Rect::new(1, 1, NonZero::new(8).unwrap(), NonZero::new(5).unwrap());
+        SortedRanges::try_from_ordered_iter_roi([0u32..12, 20..21, 23..40], roi).unwrap()
+    }
+
+    #[test]
+    fn grid_geometry() {
+        assert_eq!((3, 2), (GRID.columns(), GRID.rows()));
+        assert_eq!(
+            Rect::new(8, 4, NonZero::new(2).unwrap(), NonZero::new(3).unwrap()),
+            GRID.tile_bounds(TileIndex { row: 1, column: 2 })
+        );
+        let query = Rect::new(3, 3, NonZero::new(2).unwrap(), NonZero::new(2).unwrap());
+        assert_eq!(
+            vec![
+                TileIndex { row: 0, column: 0 },
+                TileIndex { row: 0, column: 1 },
+                TileIndex { row: 1, column: 0 },
+                TileIndex { row: 1, column: 1 },
+            ],
+            GRID.tiles_intersecting(query).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn split_and_stitch_roundtrip() {
+        let tiles = mask().split_into_tiles(GRID.tile_size()).unwrap();
+        assert_eq!(6, tiles.len());
+        assert_eq!(
+            Rect::new(1, 1, NonZero::new(3).unwrap(), NonZero::new(3).unwrap()),
+            tiles[&TileIndex { row: 0, column: 0 }].bounds()
+        );
+        assert_eq!(
+            vec![11u64..19, 21..25, 35..36, 38..39, 41..49, 51..59],
+            GRID.stitch(tiles.into_values()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn load_only_intersecting_tiles() {
+        let tiles = mask().split_into_tiles(GRID.tile_size()).unwrap();
+        let mut requested = Vec::new();
+        let mut loader = |index: TileIndex| {
+            requested.push(index);
+            if index.column == 2 {
+                return Err("unreadable");
+            }
+            Ok(tiles.get(&index).cloned())
+        };
+        let query = Rect::new(0, 4, NonZero::new(8).unwrap(), NonZero::new(3).unwrap());
+        let stitched = GRID.load(&mut loader, query).unwrap();
+        assert_eq!(vec![41u64..48, 51..58], stitched.collect::<Vec<_>>());
+        assert_eq!(
+            Err("unreadable"),
+            GRID.load(
+                &mut loader,
+                Rect::new(8, 0, NonZero::<u32>::MIN, NonZero::<u32>::MIN)
+            )
+            .map(|_| ())
+        );
+        assert_eq!(
+            vec![
+                TileIndex { row: 1, column: 0 },
+                TileIndex { row: 1, column: 1 },
+                TileIndex { row: 0, column: 2 },
+            ],
+            requested
+        );
+    }
+}
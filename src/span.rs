@@ -1,27 +1,62 @@
 use std::{
     fmt::Debug,
-    ops::{Add, Div, Mul, Rem},
+    ops::{Add, Div, Mul, Range, Rem, Sub},
 };
 
-use crate::{CreateRange, ImageDimension, NonZeroRange, UncheckedCast};
+use crate::{CreateRange, ImageDimension, IntoRoiIterator, NonZeroRange, Rect, UncheckedCast};
 
 mod clip;
 mod into_ranges;
 mod rect;
+mod sorted_spans;
 mod union;
 
 pub use clip::*;
 pub use into_ranges::*;
 pub use rect::*;
+pub use sorted_spans::*;
 pub use union::*;
 
+/// Converts a collection into spans in the coordinates of its parent image
+/// ```
+/// use std::num::NonZero;
+/// use imask::{IntoSpanIter, Rect, SortedRanges, Span};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let roi = Rect::new(2, 1, NonZero::new(4).unwrap(), NonZero::new(2).unwrap());
+/// let mask = SortedRanges::<u16, u16>::try_from_ordered_iter_roi([1u32..6], roi)?;
+/// let spans = (&mask).into_span_iter().collect::<Vec<Span<u32>>>();
+/// assert_eq!(vec![Span::new(3..6, 1), Span::new(2..4, 2)], spans);
+/// # Ok(())
+/// # }
+/// ```
 pub trait IntoSpanIter<T> {
-    type Item;
-    type IntoIter: Iterator<Item = NonZeroRange<Self::Item>> + ImageDimension;
+    type IntoIter: Iterator<Item = Span<T>> + ImageDimension;
 
     fn into_span_iter(self) -> Self::IntoIter;
 }
 
+impl<TCollection, T> IntoSpanIter<T> for TCollection
+where
+    TCollection: IntoRoiIterator<Range<T>>,
+    Range<T>: CreateRange<Item = T>,
+    T: Copy
+        + Div<Output = T>
+        + Mul<Output = T>
+        + Add<Output = T>
+        + Sub<Output = T>
+        + Rem<Output = T>
+        + Ord
+        + Debug,
+    u32: UncheckedCast<T>,
+{
+    type IntoIter = SortedRangesSpanIter<TCollection::IntoIter>;
+
+    fn into_span_iter(self) -> Self::IntoIter {
+        SortedRangesSpanIter::new(self.into_roi_iterator())
+    }
+}
+
 /// x_end is exclusive
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Ord, Eq)]
 pub struct Span<T> {
@@ -36,7 +71,8 @@ impl<T: Debug + Ord + Copy> Span<T> {
     }
 }
 
-struct SortedRangesSpanIter<TParent>
+/// Cuts ROI-local ranges at the end of each row and converts them into spans of the parent image
+pub struct SortedRangesSpanIter<TParent>
 where
     TParent: Iterator<Item: CreateRange>,
 {
@@ -45,7 +81,7 @@ where
 }
 
 impl<TParent: Iterator<Item: CreateRange>> SortedRangesSpanIter<TParent> {
-    fn new(parent: TParent) -> Self {
+    pub fn new(parent: TParent) -> Self {
         Self {
             parent,
             pending: None,
@@ -53,6 +89,18 @@ impl<TParent: Iterator<Item: CreateRange>> SortedRangesSpanIter<TParent> {
     }
 }
 
+impl<TParent: Iterator<Item: CreateRange> + ImageDimension> ImageDimension
+    for SortedRangesSpanIter<TParent>
+{
+    fn bounds(&self) -> Rect<u32> {
+        self.parent.bounds()
+    }
+
+    fn width(&self) -> std::num::NonZero<u32> {
+        self.parent.width()
+    }
+}
+
 impl<TParent> Iterator for SortedRangesSpanIter<TParent>
 where
     TParent: Iterator<
@@ -61,13 +109,13 @@ where
                           + Div<Output = <TParent::Item as CreateRange>::Item>
                           + Mul<Output = <TParent::Item as CreateRange>::Item>
                           + Add<Output = <TParent::Item as CreateRange>::Item>
+                          + Sub<Output = <TParent::Item as CreateRange>::Item>
                           + Rem<Output = <TParent::Item as CreateRange>::Item>
                           + Ord
                           + Debug,
             >,
         > + ImageDimension,
     u32: UncheckedCast<<TParent::Item as CreateRange>::Item>,
-    //NonZeroRange<<TParent::Item as CreateRange>::Item>: CreateRange,
 {
     type Item = Span<<TParent::Item as CreateRange>::Item>;
 
@@ -77,36 +125,50 @@ where
                 .next()
                 .map(|x| NonZeroRange::new_debug_checked_zeroable(x.start(), x.end()))
         })?;
-        let start = range.start();
-        let end = range.end();
-        let width = self.parent.width().get().cast_unchecked();
-        let y = start / width;
-        let cut = y * width + width;
-        let x = if let Ok(rest) = NonZeroRange::try_from(cut..end) {
-            self.pending = Some(rest);
-            NonZeroRange::new_debug_checked_zeroable(start, cut)
-        } else {
-            NonZeroRange::new_debug_checked_zeroable(start, end)
-        };
-        Some(Span { x, y })
+        let (span, rest) = cut_span(range, self.parent.bounds());
+        self.pending = rest;
+        Some(span)
     }
 }
 
-// impl<T> IntoSpanIter<T> for SortedRanges<T, T> {
-//     type Item = T;
-
-//     type IntoIter = ;
+/// Converts the part of a ROI-local `range` within its first row into a span of the parent image.
+/// Returns the remainder, if `range` continues on the next row
+pub(crate) fn cut_span<T>(
+    range: NonZeroRange<T>,
+    roi: Rect<u32>,
+) -> (Span<T>, Option<NonZeroRange<T>>)
+where
+    T: Copy
+        + Div<Output = T>
+        + Mul<Output = T>
+        + Add<Output = T>
+        + Sub<Output = T>
+        + Rem<Output = T>
+        + Ord
+        + Debug,
+    u32: UncheckedCast<T>,
+{
+    let (start, end) = (range.start(), range.end());
+    let width: T = roi.width.get().cast_unchecked();
+    let offset_x: T = roi.x.cast_unchecked();
+    let row = start / width;
+    let row_start = row * width;
+    let cut = row_start + width;
+    let y = row + roi.y.cast_unchecked();
+    let x_start = start % width + offset_x;
+    let (x_end, rest) = match NonZeroRange::try_from(cut..end) {
+        Ok(rest) => (width + offset_x, Some(rest)),
+        Err(_) => (end - row_start + offset_x, None),
+    };
+    let x = NonZeroRange::new_debug_checked_zeroable(x_start, x_end);
+    (Span { x, y }, rest)
+}
 
-//     fn into_span_iter(self) {
-//         self.iter_roi_owned()
-//         todo!()
-//     }
-// }
 #[cfg(test)]
 mod tests {
     use std::num::NonZeroU32;
 
-    use crate::ImaskSet;
+    use crate::{ImaskSet, SortedRanges, SortedRangesMap};
 
     use super::*;
 
@@ -117,7 +179,7 @@ mod tests {
         let iter = [0u32..10, 11..20].with_bounds(NONZERO_10, NONZERO_10);
         let span = SortedRangesSpanIter::new(iter);
         assert_eq!(
-            vec!(Span::new(0..10, 0), Span::new(11..20, 1)),
+            vec!(Span::new(0..10, 0), Span::new(1..10, 1)),
             span.collect::<Vec<_>>()
         );
     }
@@ -126,8 +188,43 @@ mod tests {
         let iter = [0u32..20].with_bounds(NONZERO_10, NONZERO_10);
         let span = SortedRangesSpanIter::new(iter);
         assert_eq!(
-            vec!(Span::new(0..10, 0), Span::new(10..20, 1)),
+            vec!(Span::new(0..10, 0), Span::new(0..10, 1)),
             span.collect::<Vec<_>>()
         );
     }
+
+    #[test]
+    fn roundtrip_with_roi_offset() {
+        let roi = Rect::new(3, 2, NONZERO_10, NONZERO_10);
+        let mask =
+            SortedRanges::<u16, u16>::try_from_ordered_iter_roi([5u32..25, 30..31], roi).unwrap();
+        let spans = (&mask).into_span_iter().collect::<Vec<Span<u32>>>();
+        assert_eq!(
+            vec![
+                Span::new(8..13, 2),
+                Span::new(3..13, 3),
+                Span::new(3..8, 4),
+                Span::new(3..4, 5)
+            ],
+            spans
+        );
+        let ranges = (&mask)
+            .into_span_iter()
+            .into_ranges::<Range<u32>>()
+            .collect::<Vec<_>>();
+        assert_eq!(vec![5..25, 30..31], ranges);
+    }
+
+    #[test]
+    fn map_spans_ignore_meta() {
+        let map = SortedRangesMap::<u16, u16, Vec<u8>>::try_from_ordered_iter(
+            [(8u32..12, 1), (13..14, 2)].with_bounds(NONZERO_10, NONZERO_10),
+        )
+        .unwrap();
+        let spans = map.into_span_iter().collect::<Vec<Span<u64>>>();
+        assert_eq!(
+            vec![Span::new(8..10, 0), Span::new(0..2, 1), Span::new(3..4, 1)],
+            spans
+        );
+    }
 }
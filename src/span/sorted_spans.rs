@@ -0,0 +1,320 @@
+use std::{
+    fmt::{Debug, Display},
+    iter::FusedIterator,
+    num::NonZero,
+    ops::Range,
+};
+
+use crate::{
+    BuildError, CreateRange, ImageDimension, IntoSpanIter, NonZeroRange, Rect, SortedRanges, Span,
+    UncheckedCast,
+};
+
+/// Stores x-ranges grouped by row (like CSR), so the ranges of a row can be found in O(1).
+/// An alternative to `SortedRanges`, if row access dominates. X-ranges are relative to the ROI
+/// ```
+/// use std::num::NonZero;
+/// use imask::{NonZeroRange, Rect, SortedRanges, SortedSpans, Span};
+///
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let roi = Rect::new(2, 1, NonZero::new(4).unwrap(), NonZero::new(3).unwrap());
+/// let mask = SortedRanges::<u16, u16>::try_from_ordered_iter_roi([1u32..6, 9..11], roi)?;
+/// let spans = SortedSpans::<u16>::from_sorted_ranges(&mask)?;
+/// assert_eq!(Some(&[NonZeroRange::new(0u16..2)][..]), spans.row(1));
+/// assert_eq!(Some(&[NonZeroRange::new(1u16..3)][..]), spans.row(2));
+/// assert_eq!(
+///     vec![Span::new(3..6, 1), Span::new(2..4, 2), Span::new(3..5, 3)],
+///     spans.iter().collect::<Vec<Span<u32>>>()
+/// );
+/// assert_eq!(mask, spans.to_sorted_ranges()?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortedSpans<T> {
+    /// `ranges[row_starts[y]..row_starts[y + 1]]` are the ranges of ROI row `y`
+    row_starts: Vec<usize>,
+    ranges: Vec<NonZeroRange<T>>,
+    bounds: Rect<u32>,
+}
+
+impl<T> SortedSpans<T> {
+    pub fn empty(bounds: Rect<u32>) -> Self {
+        Self {
+            row_starts: vec![0; bounds.height.get() as usize + 1],
+            ranges: Vec::new(),
+            bounds,
+        }
+    }
+
+    /// Number of spans
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// X-ranges of `row` relative to the ROI. Returns None, if `row` is outside of the ROI
+    pub fn row(&self, row: u32) -> Option<&[NonZeroRange<T>]> {
+        let row = row as usize;
+        let start = *self.row_starts.get(row)?;
+        let end = *self.row_starts.get(row + 1)?;
+        Some(&self.ranges[start..end])
+    }
+
+    /// Spans in the coordinates of the parent image
+    pub fn iter(&self) -> SortedSpansIter<'_, T> {
+        SortedSpansIter {
+            spans: self,
+            row: 0,
+            index: 0,
+        }
+    }
+}
+
+impl<T> SortedSpans<T>
+where
+    T: UncheckedCast<u64> + TryFrom<u64, Error: Display> + Ord + Debug,
+{
+    /// Collects sorted spans in the coordinates of the parent image. Spans of the same row
+    /// must not overlap or touch and all spans must be within the ROI
+    pub fn try_from_ordered_spans<TSpan: Into<u64> + Copy>(
+        iter: impl IntoIterator<Item = Span<TSpan>, IntoIter: ImageDimension>,
+    ) -> Result<Self, BuildError> {
+        let iter = iter.into_iter();
+        let bounds = iter.bounds();
+        let (width, height) = (
+            u64::from(bounds.width.get()),
+            u64::from(bounds.height.get()),
+        );
+        let (offset_x, offset_y) = (u64::from(bounds.x), u64::from(bounds.y));
+        let mut row_starts = Vec::with_capacity(height as usize + 1);
+        row_starts.push(0);
+        let mut ranges = Vec::with_capacity(iter.size_hint().0);
+        let mut previous: Option<(u64, u64)> = None;
+        for span in iter {
+            let (start, end, y): (u64, u64, u64) =
+                (span.x.start.into(), span.x.end.into(), span.y.into());
+            let (Some(x), Some(row)) = (start.checked_sub(offset_x), y.checked_sub(offset_y))
+            else {
+                return Err(BuildError::InvalidPosition(format!(
+                    "Span at ({start}, {y}) starts before the ROI"
+                )));
+            };
+            let x_end = end - offset_x;
+            if row >= height || x_end > width {
+                return Err(BuildError::OutOfBounds {
+                    end: row * width + x_end,
+                    size: width * height,
+                });
+            }
+            if let Some((previous_row, previous_end)) = previous
+                && (row < previous_row || (row == previous_row && x <= previous_end))
+            {
+                return Err(BuildError::Overlap {
+                    start: row * width + x,
+                    previous_end: previous_row * width + previous_end,
+                });
+            }
+            while row_starts.len() as u64 <= row {
+                row_starts.push(ranges.len());
+            }
+            ranges.push(NonZeroRange::new_debug_checked_zeroable(
+                convert(x)?,
+                convert(x_end)?,
+            ));
+            previous = Some((row, x_end));
+        }
+        row_starts.resize(height as usize + 1, ranges.len());
+        Ok(Self {
+            row_starts,
+            ranges,
+            bounds,
+        })
+    }
+
+    pub fn from_sorted_ranges<TIncluded, TExcluded>(
+        mask: &SortedRanges<TIncluded, TExcluded>,
+    ) -> Result<Self, BuildError>
+    where
+        TIncluded: UncheckedCast<u64>,
+        TExcluded: UncheckedCast<u64>,
+    {
+        Self::try_from_ordered_spans(IntoSpanIter::<u64>::into_span_iter(mask))
+    }
+
+    /// Ranges of the last column touching ranges of the first column in the next row are merged
+    pub fn to_sorted_ranges<TIncluded, TExcluded>(
+        &self,
+    ) -> Result<SortedRanges<TIncluded, TExcluded>, BuildError>
+    where
+        TIncluded: TryFrom<u64, Error: Display>,
+        TExcluded: TryFrom<u64, Error: Display>,
+    {
+        let width = u64::from(self.bounds.width.get());
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(self.ranges.len());
+        for (row, window) in (0u64..).zip(self.row_starts.windows(2)) {
+            for range in &self.ranges[window[0]..window[1]] {
+                let offset = row * width;
+                let range =
+                    offset + range.start.cast_unchecked()..offset + range.end.cast_unchecked();
+                match merged.last_mut() {
+                    Some(last) if last.end == range.start => last.end = range.end,
+                    _ => merged.push(range),
+                }
+            }
+        }
+        SortedRanges::try_from_ordered_iter_roi(merged, self.bounds)
+    }
+}
+
+fn convert<T: TryFrom<u64, Error: Display>>(value: u64) -> Result<T, BuildError> {
+    T::try_from(value).map_err(|e| BuildError::InvalidPosition(e.to_string()))
+}
+
+impl<T> ImageDimension for SortedSpans<T> {
+    fn bounds(&self) -> Rect<u32> {
+        self.bounds
+    }
+
+    fn width(&self) -> NonZero<u32> {
+        self.bounds.width
+    }
+}
+
+impl<'a, T: UncheckedCast<u32>> IntoSpanIter<u32> for &'a SortedSpans<T> {
+    type IntoIter = SortedSpansIter<'a, T>;
+
+    fn into_span_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// See `SortedSpans::iter`
+#[derive(Clone)]
+pub struct SortedSpansIter<'a, T> {
+    spans: &'a SortedSpans<T>,
+    row: usize,
+    index: usize,
+}
+
+impl<T: UncheckedCast<u32>> Iterator for SortedSpansIter<'_, T> {
+    type Item = Span<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let range = self.spans.ranges.get(self.index)?;
+        while self.spans.row_starts[self.row + 1] <= self.index {
+            self.row += 1;
+        }
+        self.index += 1;
+        let bounds = self.spans.bounds;
+        let x = NonZeroRange::new_debug_checked_zeroable(
+            bounds.x + range.start.cast_unchecked(),
+            bounds.x + range.end.cast_unchecked(),
+        );
+        Some(Span {
+            x,
+            y: bounds.y + self.row as u32,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.spans.ranges.len() - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<T: UncheckedCast<u32>> ExactSizeIterator for SortedSpansIter<'_, T> {}
+impl<T: UncheckedCast<u32>> FusedIterator for SortedSpansIter<'_, T> {}
+
+impl<T> ImageDimension for SortedSpansIter<'_, T> {
+    fn bounds(&self) -> Rect<u32> {
+        self.spans.bounds
+    }
+
+    fn width(&self) -> NonZero<u32> {
+        self.spans.bounds.width
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ImaskSet;
+
+    use super::*;
+
+    const SIZE: NonZero<u32> = NonZero::new(10).unwrap();
+
+    #[test]
+    fn row_lookup_with_empty_rows() {
+        let roi = Rect::new(0, 0, SIZE, SIZE);
+        let mask = SortedRanges::<u8, u8>::try_from_ordered_iter_roi(
+            [2u32..4, 6..8, 35..45, 99..100],
+            roi,
+        )
+        .unwrap();
+        let spans = SortedSpans::<u8>::from_sorted_ranges(&mask).unwrap();
+        assert_eq!(5, spans.len());
+        let row = |y| {
+            spans
+                .row(y)
+                .unwrap()
+                .iter()
+                .map(|r| Range::from(*r))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![2..4, 6..8], row(0));
+        assert!(row(1).is_empty());
+        assert_eq!(vec![5..10], row(3));
+        assert_eq!(vec![0..5], row(4));
+        assert_eq!(vec![9..10], row(9));
+        assert_eq!(None, spans.row(10));
+        // Ranges crossing rows are merged again
+        assert_eq!(mask, spans.to_sorted_ranges().unwrap());
+    }
+
+    #[test]
+    fn spans_roundtrip_into_ranges() {
+        let roi = Rect::new(5, 3, SIZE, SIZE);
+        let mask =
+            SortedRanges::<u16, u16>::try_from_ordered_iter_roi([8u32..23, 50..51], roi).unwrap();
+        let spans = SortedSpans::<u16>::from_sorted_ranges(&mask).unwrap();
+        let iter = spans.iter();
+        assert_eq!(4, iter.len());
+        assert_eq!(roi, iter.bounds());
+        let ranges = iter.into_ranges::<Range<u32>>().collect::<Vec<_>>();
+        assert_eq!(vec![8..23, 50..51], ranges);
+    }
+
+    #[test]
+    fn invalid_spans() {
+        let roi = Rect::new(5, 5, SIZE, SIZE);
+        let build = |spans: Vec<Span<u32>>| {
+            SortedSpans::<u8>::try_from_ordered_spans(spans.with_roi(roi)).unwrap_err()
+        };
+        assert_eq!(
+            BuildError::Overlap {
+                start: 4,
+                previous_end: 4
+            },
+            build(vec![Span::new(5..9, 5), Span::new(9..10, 5)])
+        );
+        assert_eq!(
+            BuildError::Overlap {
+                start: 10,
+                previous_end: 24
+            },
+            build(vec![Span::new(5..9, 7), Span::new(5..6, 6)])
+        );
+        assert_eq!(
+            BuildError::OutOfBounds { end: 11, size: 100 },
+            build(vec![Span::new(5..16, 5)])
+        );
+        assert!(matches!(
+            build(vec![Span::new(4..6, 5)]),
+            BuildError::InvalidPosition(_)
+        ));
+    }
+}